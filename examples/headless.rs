@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::prelude::*;
+use rs_boids::{Boid, BoidsPlugin};
+
+// Runs the simulation without a window or renderer and exits after a fixed number of frames.
+fn main() {
+    App::new()
+        .add_plugins(
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+                1.0 / 60.0,
+            ))),
+        )
+        .add_plugins(BoidsPlugin {
+            spawn_count: 1000,
+            bounds: Some(Rect::new(-640.0, -360.0, 640.0, 360.0)),
+            ..BoidsPlugin::headless()
+        })
+        .add_systems(Update, report)
+        .run();
+}
+
+fn report(mut frames: Local<u32>, boids: Query<&Boid>, mut exit: EventWriter<AppExit>) {
+    *frames += 1;
+
+    if frames.is_multiple_of(60) {
+        let count = boids.iter().count();
+        let center = boids.iter().map(|boid| boid.position).sum::<Vec2>() / count.max(1) as f32;
+        println!("frame {} boids {} center {:?}", *frames, count, center);
+    }
+
+    if *frames >= 600 {
+        exit.send(AppExit::Success);
+    }
+}
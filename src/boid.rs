@@ -0,0 +1,291 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_egui::egui::lerp;
+use rand::Rng;
+
+use crate::boundary;
+use crate::config::{BoidConfiguration, SpeciesConfig};
+use crate::rng::BoidRng;
+
+#[derive(Component, Default)]
+pub struct Boid {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub initial_color: Color,
+}
+
+/// Index into `BoidConfiguration::species`.
+#[derive(Component, Clone, Copy, Default, Debug, PartialEq, Eq, Hash, Deref)]
+pub struct Species(pub usize);
+
+/// Identifies a boid for its whole life. Unlike `Entity`, ids are never reused, so a boid
+/// despawned by `boid_ensure_count` and its replacement can be told apart in exported data.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deref)]
+pub struct BoidId(pub u64);
+
+/// The id `boid_assign_ids` hands out next.
+#[derive(Resource, Default, Debug)]
+pub struct NextBoidId(pub u64);
+
+/// How many neighbours the boid aligned and cohered with on the last tick.
+#[derive(Component, Clone, Copy, Default, Debug, PartialEq, Eq, Deref)]
+pub struct Neighbors(pub u32);
+
+/// Position at the start of the last simulation tick, used to interpolate the transform between
+/// ticks.
+#[derive(Component, Default, Deref, DerefMut)]
+pub struct PreviousPosition(pub Vec2);
+
+pub fn setup(
+    config: BoidConfiguration,
+    spawn_count: u32,
+    bounds: Option<Rect>,
+    seed: Option<u64>,
+) -> impl FnMut(Commands, Query<&Window, With<PrimaryWindow>>) {
+    move |mut commands, window| {
+        let boid_bounds = bounds
+            .or_else(|| {
+                window.get_single().ok().map(|window| {
+                    Rect::new(
+                        -window.width() / 2.0,
+                        -window.height() / 2.0,
+                        window.width() / 2.0,
+                        window.height() / 2.0,
+                    )
+                })
+            })
+            .unwrap_or(config.boid_bounds);
+
+        let seed = seed.unwrap_or_else(rand::random);
+
+        let mut config: BoidConfiguration = BoidConfiguration {
+            boid_bounds,
+            seed,
+            ..config.clone()
+        };
+        config.enforce_limits();
+
+        // the plugin's count goes to the first species
+        match config.species.first_mut() {
+            Some(species) => species.spawn_count = spawn_count,
+            None => config.add_species(SpeciesConfig {
+                spawn_count,
+                ..default()
+            }),
+        }
+
+        commands.spawn_empty().insert(config);
+        commands.insert_resource(BoidRng::new(seed));
+    }
+}
+
+/// Removes a species from the configuration, despawning its boids and renumbering the rest.
+#[derive(Event)]
+pub struct RemoveSpecies(pub usize);
+
+pub fn boid_remove_species(
+    mut commands: Commands,
+    mut removals: EventReader<RemoveSpecies>,
+    mut config: Query<&mut BoidConfiguration>,
+    mut boids: Query<(Entity, &mut Species)>,
+) {
+    let mut config = config.single_mut();
+
+    for RemoveSpecies(index) in removals.read() {
+        config.remove_species(*index);
+
+        for (entity, mut species) in boids.iter_mut() {
+            if species.0 == *index {
+                commands.entity(entity).despawn_recursive();
+            } else if species.0 > *index {
+                species.0 -= 1;
+            }
+        }
+    }
+}
+
+pub fn boid_ensure_count(
+    mut commands: Commands,
+    mut rng: ResMut<BoidRng>,
+    mut config: Query<&mut BoidConfiguration>,
+    boids: Query<(Entity, &Species), With<Boid>>,
+) {
+    let mut config = config.single_mut();
+
+    let mut counts = vec![0u32; config.species.len()];
+    for (entity, species) in boids.iter() {
+        match config.species.get(species.0) {
+            Some(species_config) if counts[species.0] < species_config.spawn_count => {
+                counts[species.0] += 1;
+            }
+            // too many of this species, or a species that was removed
+            _ => commands.entity(entity).despawn_recursive(),
+        }
+    }
+
+    for (species, current) in counts.into_iter().enumerate() {
+        for _ in current..config.species[species].spawn_count {
+            spawn_boid(&mut commands, &mut config, species, &mut rng.simulation);
+        }
+    }
+}
+
+/// Gives new boids the next ids, in entity order so a seed always numbers its flock the same.
+pub fn boid_assign_ids(
+    mut commands: Commands,
+    mut next: ResMut<NextBoidId>,
+    boids: Query<Entity, (With<Boid>, Without<BoidId>)>,
+) {
+    let mut boids = boids.iter().collect::<Vec<_>>();
+    boids.sort();
+
+    for entity in boids {
+        commands.entity(entity).insert(BoidId(next.0));
+        next.0 += 1;
+    }
+}
+
+pub fn spawn_initial(
+    mut commands: Commands,
+    mut rng: ResMut<BoidRng>,
+    mut config: Query<&mut BoidConfiguration>,
+) {
+    let mut config = config.single_mut();
+    for species in 0..config.species.len() {
+        for _ in 0..config.species[species].spawn_count {
+            spawn_boid(&mut commands, &mut config, species, &mut rng.simulation)
+        }
+    }
+}
+
+/// Spawns the simulation side of a boid. Meshes and materials are attached separately by
+/// `render::boid_attach_visuals` so this works without a renderer.
+pub fn spawn_boid(
+    commands: &mut Commands,
+    config: &mut BoidConfiguration,
+    species: usize,
+    rng: &mut impl Rng,
+) {
+    let area = config.spawn_range;
+    spawn_boid_in(commands, config, species, area, rng);
+}
+
+/// Like `spawn_boid`, placing the boid somewhere in `area` instead of `spawn_range`.
+pub fn spawn_boid_in(
+    commands: &mut Commands,
+    config: &mut BoidConfiguration,
+    species: usize,
+    area: Rect,
+    rng: &mut impl Rng,
+) {
+    let species_config = &config.species[species];
+    let max_speed = species_config.max_speed;
+    let [r, g, b] = species_config.color_rgb;
+    let initial_color = Color::srgb(
+        r * rng.random::<f32>(),
+        g * rng.random::<f32>(),
+        b * rng.random::<f32>(),
+    );
+
+    let position = Vec2::new(
+        lerp(area.min.x..=area.max.x, rng.random::<f32>()),
+        lerp(area.min.y..=area.max.y, rng.random::<f32>()),
+    );
+
+    let boid = Boid {
+        initial_color,
+        position,
+        velocity: Vec2 {
+            x: lerp(-max_speed..=max_speed, rng.random::<f32>()),
+            y: lerp(-max_speed..=max_speed, rng.random::<f32>()),
+        },
+    };
+
+    spawn_boid_exact(commands, config, species, boid);
+}
+
+/// Spawns `boid` as it is, without drawing anything from the rng.
+pub fn spawn_boid_exact(
+    commands: &mut Commands,
+    config: &mut BoidConfiguration,
+    species: usize,
+    boid: Boid,
+) -> Entity {
+    let entity = commands.spawn_empty().id();
+    let position = boid.position;
+
+    commands.entity(entity).insert(Name::new("boid"));
+
+    commands.entity(entity).insert(Transform::from_xyz(
+        position.x,
+        position.y,
+        // use the entity index for the z value to prevent (war) z-fighting
+        entity.index() as f32 * 0.001,
+    ));
+
+    commands.entity(entity).insert(PreviousPosition(position));
+
+    commands.entity(entity).insert(boid);
+
+    commands
+        .entity(entity)
+        .insert((Species(species), Neighbors::default()));
+
+    config.total_boids += 1;
+
+    entity
+}
+
+pub fn boid_movement(time: Res<Time>, mut boids: Query<(&mut Boid, &mut PreviousPosition)>) {
+    for (mut boid, mut previous) in boids.iter_mut() {
+        previous.0 = boid.position;
+        let velocity = boid.velocity;
+        boid.position += velocity * time.delta().as_secs_f32();
+    }
+}
+
+pub fn boid_speed_up(
+    time: Res<Time>,
+    mut boids: Query<(&mut Boid, &Species)>,
+    config: Query<&BoidConfiguration>,
+) {
+    let config = config.single();
+    for (mut boid, species) in boids.iter_mut() {
+        let Some(species) = config.species.get(species.0) else {
+            continue;
+        };
+
+        if boid.velocity.length() <= species.max_speed {
+            boid.velocity = boid.velocity.lerp(
+                boid.velocity.normalize() * species.max_speed,
+                time.delta_secs(),
+            );
+        }
+
+        boid.velocity = boid
+            .velocity
+            .clamp_length(species.min_speed, species.max_speed);
+    }
+}
+
+pub fn boid_turn_factor(config: Query<&BoidConfiguration>, mut boids: Query<&mut Boid>) {
+    let config = config.single();
+    for mut boid in boids.iter_mut() {
+        let steer = boundary::steer(config, boid.position);
+        boid.velocity += steer;
+    }
+}
+
+/// Wraps or bounces boids that left the bounds, after they have moved.
+pub fn boid_confine(
+    config: Query<&BoidConfiguration>,
+    mut boids: Query<(&mut Boid, &mut PreviousPosition)>,
+) {
+    let config = config.single();
+    for (mut boid, mut previous) in boids.iter_mut() {
+        let Boid {
+            position, velocity, ..
+        } = &mut *boid;
+        previous.0 += boundary::confine(config, position, velocity);
+    }
+}
@@ -0,0 +1,168 @@
+use bevy::prelude::*;
+
+use crate::config::{BoidConfiguration, BoundaryMode};
+
+/// Velocity change that turns something at `position` back towards the bounds, for the modes that
+/// steer.
+pub fn steer(config: &BoidConfiguration, position: Vec2) -> Vec2 {
+    let bounds = config.boid_bounds;
+    let mut steer = Vec2::ZERO;
+
+    match config.boundary_mode {
+        BoundaryMode::SoftMargin => {
+            if position.x < bounds.min.x {
+                steer.x += config.turn_factor;
+            }
+
+            if position.x > bounds.max.x {
+                steer.x -= config.turn_factor;
+            }
+
+            if position.y < bounds.min.y {
+                steer.y += config.turn_factor;
+            }
+
+            if position.y > bounds.max.y {
+                steer.y -= config.turn_factor;
+            }
+        }
+        BoundaryMode::Circular => {
+            let offset = position - bounds.center();
+            if offset.length() > bounds.half_size().min_element() {
+                steer -= offset.normalize_or_zero() * config.turn_factor;
+            }
+        }
+        BoundaryMode::Wrap | BoundaryMode::Reflect | BoundaryMode::None => {}
+    }
+
+    steer
+}
+
+/// `to - from`, going across the seams when `wrap` is set and that way is shorter.
+pub fn offset(wrap: Option<Rect>, from: Vec2, to: Vec2) -> Vec2 {
+    let offset = to - from;
+    match wrap {
+        Some(bounds) if bounds.size().min_element() > 0.0 => {
+            let size = bounds.size();
+            (offset + size / 2.0).rem_euclid(size) - size / 2.0
+        }
+        _ => offset,
+    }
+}
+
+/// Puts something that left the bounds back inside, for the modes that don't steer.
+///
+/// Returns how far wrapping teleported it, so the caller can move `PreviousPosition` along too and
+/// the interpolated transform doesn't sweep across the screen.
+pub fn confine(config: &BoidConfiguration, position: &mut Vec2, velocity: &mut Vec2) -> Vec2 {
+    let bounds = config.boid_bounds;
+    if bounds.size().min_element() <= 0.0 {
+        return Vec2::ZERO;
+    }
+
+    match config.boundary_mode {
+        BoundaryMode::Wrap => {
+            let wrapped = bounds.min + (*position - bounds.min).rem_euclid(bounds.size());
+            let shift = wrapped - *position;
+            *position = wrapped;
+            shift
+        }
+        BoundaryMode::Reflect => {
+            if position.x < bounds.min.x {
+                position.x = 2.0 * bounds.min.x - position.x;
+                velocity.x = velocity.x.abs();
+            }
+
+            if position.x > bounds.max.x {
+                position.x = 2.0 * bounds.max.x - position.x;
+                velocity.x = -velocity.x.abs();
+            }
+
+            if position.y < bounds.min.y {
+                position.y = 2.0 * bounds.min.y - position.y;
+                velocity.y = velocity.y.abs();
+            }
+
+            if position.y > bounds.max.y {
+                position.y = 2.0 * bounds.max.y - position.y;
+                velocity.y = -velocity.y.abs();
+            }
+
+            // anything that was further out than the bounds are wide
+            *position = position.clamp(bounds.min, bounds.max);
+            Vec2::ZERO
+        }
+        BoundaryMode::SoftMargin | BoundaryMode::Circular | BoundaryMode::None => Vec2::ZERO,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::prelude::*;
+
+    use crate::boundary::{confine, offset, steer};
+    use crate::config::{BoidConfiguration, BoundaryMode};
+
+    fn config(boundary_mode: BoundaryMode) -> BoidConfiguration {
+        BoidConfiguration {
+            boid_bounds: Rect::new(-100.0, -50.0, 100.0, 50.0),
+            turn_factor: 1.0,
+            boundary_mode,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn wrap() {
+        let config = config(BoundaryMode::Wrap);
+        let mut position = Vec2::new(105.0, -60.0);
+        let mut velocity = Vec2::X;
+
+        let shift = confine(&config, &mut position, &mut velocity);
+        assert!(position.abs_diff_eq(Vec2::new(-95.0, 40.0), 1e-4));
+        assert!(shift.abs_diff_eq(Vec2::new(-200.0, 100.0), 1e-4));
+        assert_eq!(velocity, Vec2::X);
+    }
+
+    #[test]
+    fn reflect() {
+        let config = config(BoundaryMode::Reflect);
+        let mut position = Vec2::new(105.0, 0.0);
+        let mut velocity = Vec2::new(3.0, 1.0);
+
+        assert_eq!(confine(&config, &mut position, &mut velocity), Vec2::ZERO);
+        assert!(position.abs_diff_eq(Vec2::new(95.0, 0.0), 1e-4));
+        assert_eq!(velocity, Vec2::new(-3.0, 1.0));
+    }
+
+    #[test]
+    fn steering() {
+        let soft = config(BoundaryMode::SoftMargin);
+        assert_eq!(steer(&soft, Vec2::new(110.0, 0.0)), Vec2::NEG_X);
+        assert_eq!(steer(&soft, Vec2::new(90.0, 0.0)), Vec2::ZERO);
+
+        let circle = config(BoundaryMode::Circular);
+        assert_eq!(
+            steer(&circle, Vec2::new(60.0, 0.0)),
+            Vec2::NEG_X,
+            "outside the circle"
+        );
+        assert_eq!(steer(&circle, Vec2::new(40.0, 0.0)), Vec2::ZERO);
+
+        assert_eq!(
+            steer(&config(BoundaryMode::None), Vec2::new(1000.0, 0.0)),
+            Vec2::ZERO
+        );
+    }
+
+    #[test]
+    fn offset_across_seams() {
+        let bounds = Rect::new(-100.0, -50.0, 100.0, 50.0);
+        let from = Vec2::new(95.0, 0.0);
+        let to = Vec2::new(-95.0, 40.0);
+
+        assert_eq!(offset(None, from, to), Vec2::new(-190.0, 40.0));
+        assert_eq!(offset(Some(bounds), from, to), Vec2::new(10.0, 40.0));
+        assert_eq!(offset(Some(bounds), to, from), Vec2::new(-10.0, -40.0));
+    }
+}
@@ -0,0 +1,169 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use clap::{Parser, ValueEnum};
+use rs_boids::preset::{self, PresetError};
+use rs_boids::{BoidConfiguration, BoidsPlugin, ExportSettings, SpatialState};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SpatialIndexArg {
+    Quadtree,
+    SpatialHash,
+    UniformGrid,
+}
+
+impl From<SpatialIndexArg> for SpatialState {
+    fn from(arg: SpatialIndexArg) -> Self {
+        match arg {
+            SpatialIndexArg::Quadtree => SpatialState::QuadTree,
+            SpatialIndexArg::SpatialHash => SpatialState::SpatialHash,
+            SpatialIndexArg::UniformGrid => SpatialState::UniformGrid,
+        }
+    }
+}
+
+/// Boid flocking simulation. Anything not given starts from `BoidConfiguration::default()`, or
+/// from the preset when there is one.
+#[derive(Parser, Debug)]
+#[command(version)]
+pub struct Args {
+    /// Number of boids of the first species
+    #[arg(short = 'n', long)]
+    pub count: Option<u32>,
+
+    /// Seed for the simulation, to reproduce a previous run
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    #[arg(long, value_enum, default_value_t = SpatialIndexArg::SpatialHash)]
+    pub spatial_index: SpatialIndexArg,
+
+    /// RON preset to start from, as saved from the presets section
+    #[arg(long)]
+    pub preset: Option<PathBuf>,
+
+    /// Preset to watch and apply whenever it is saved, as a path under assets/, e.g.
+    /// presets/tuning.ron
+    #[arg(long, value_name = "ASSET_PATH")]
+    pub watch: Option<String>,
+
+    /// Size of the simulation bounds centred on the origin, e.g. 1600x900. Defaults to the window
+    /// size
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_size)]
+    pub bounds: Option<Vec2>,
+
+    /// Size of the window, e.g. 1280x720
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_size)]
+    pub window: Option<Vec2>,
+
+    /// Export trajectories from the start, as CSV for a .csv path and columnar otherwise
+    #[arg(long, value_name = "PATH")]
+    pub export: Option<PathBuf>,
+
+    /// Ticks between exported samples
+    #[arg(long, value_name = "TICKS", default_value_t = 1)]
+    pub export_every: u32,
+
+    /// Run the simulation without a window, UI or rendering
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Exit after this many seconds of simulated time
+    #[arg(long, value_name = "SECONDS")]
+    pub duration: Option<f32>,
+}
+
+impl Args {
+    /// The configuration to start from: the preset if there is one, otherwise the default.
+    pub fn config(&self) -> Result<BoidConfiguration, PresetError> {
+        let mut config = BoidConfiguration::default();
+        if let Some(path) = &self.preset {
+            preset::apply(&mut config, preset::load_file(path)?);
+        }
+        Ok(config)
+    }
+
+    pub fn plugin(&self, config: BoidConfiguration) -> BoidsPlugin {
+        let spawn_count = self.count.unwrap_or_else(|| {
+            config
+                .species
+                .first()
+                .map_or(0, |species| species.spawn_count)
+        });
+
+        BoidsPlugin {
+            spatial_state: self.spatial_index.into(),
+            spawn_count,
+            bounds: self
+                .bounds
+                .map(|size| Rect::from_center_size(Vec2::ZERO, size)),
+            seed: self.seed,
+            config_file: self.watch.clone(),
+            export: self.export.as_ref().map(|path| ExportSettings {
+                every: self.export_every,
+                ..ExportSettings::new(path)
+            }),
+            metrics: !self.headless,
+            headless: self.headless,
+            config,
+            ..default()
+        }
+    }
+}
+
+fn parse_size(text: &str) -> Result<Vec2, String> {
+    let (width, height) = text
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, got {:?}", text))?;
+
+    let parse = |value: &str| {
+        value
+            .trim()
+            .parse::<f32>()
+            .ok()
+            .filter(|value| *value > 0.0)
+            .ok_or_else(|| format!("{:?} is not a positive number", value))
+    };
+
+    Ok(Vec2::new(parse(width)?, parse(height)?))
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::prelude::*;
+    use clap::Parser;
+    use rs_boids::SpatialState;
+
+    use crate::cli::Args;
+
+    #[test]
+    fn parses_args() {
+        let args = Args::try_parse_from([
+            "rs-boids",
+            "-n",
+            "300",
+            "--seed",
+            "7",
+            "--spatial-index",
+            "uniform-grid",
+            "--bounds",
+            "800x600",
+            "--headless",
+            "--duration",
+            "2.5",
+        ])
+        .unwrap();
+
+        let plugin = args.plugin(args.config().unwrap());
+        assert_eq!(plugin.spawn_count, 300);
+        assert_eq!(plugin.seed, Some(7));
+        assert_eq!(plugin.spatial_state, SpatialState::UniformGrid);
+        assert_eq!(plugin.bounds, Some(Rect::new(-400.0, -300.0, 400.0, 300.0)));
+        assert!(plugin.headless);
+        assert!(!plugin.metrics);
+        assert_eq!(args.duration, Some(2.5));
+
+        assert!(Args::try_parse_from(["rs-boids", "--window", "800"]).is_err());
+        assert!(Args::try_parse_from(["rs-boids", "--bounds", "0x600"]).is_err());
+    }
+}
@@ -1,29 +1,86 @@
 use bevy::{
+    asset::Asset,
     ecs::component::Component,
     math::{Rect, Vec2},
+    reflect::TypePath,
 };
+use serde::{Deserialize, Serialize};
 
-#[derive(Component, Debug)]
+/// Smallest `spatial_hash_size` the simulation accepts. Smaller cells make the spatial hash and
+/// uniform grid scan huge numbers of cells for each query.
+pub const MIN_SPATIAL_HASH_SIZE: u32 = 8;
+
+/// The `tick_rate` range the UI offers and `enforce_limits` holds files to. `Time::<Fixed>` can't
+/// take a rate that isn't positive and finite.
+pub const TICK_RATES: std::ops::RangeInclusive<f64> = 10.0..=240.0;
+
+/// Everything that tunes the simulation.
+///
+/// Saved presets leave out `total_boids` and the bounds, which belong to the window rather than
+/// the tuning. Each species' `spawn_count` is saved, so a preset does set how many boids there
+/// are. Missing fields take their defaults when loading.
+#[derive(Asset, Component, Clone, Debug, PartialEq, Serialize, Deserialize, TypePath)]
+#[serde(default)]
 pub struct BoidConfiguration {
+    #[serde(skip)]
     pub total_boids: u32,
-    pub spawn_count: u32,
+    #[serde(skip)]
     pub spawn_range: Rect,
     pub turn_factor: f32,
+    #[serde(skip)]
     pub boid_bounds: Rect,
-    pub visible_range: f32,
-    pub protected_range: f32,
-    pub avoid_factor: f32,
-    pub centering_factor: f32,
-    pub matching_factor: f32,
-    pub max_speed: f32,
-    pub min_speed: f32,
+    pub boundary_mode: BoundaryMode,
+    /// Per-species ranges, factors and speeds. `Species` on each boid indexes into this.
+    pub species: Vec<SpeciesConfig>,
+    /// `interactions[a][b]` is how species `a` treats species `b`.
+    pub interactions: Vec<Vec<Interaction>>,
+    pub neighbor_mode: NeighborMode,
+    /// Full width of the view cone in degrees, centred on the heading. Neighbours outside it are
+    /// ignored; 360 means no blind spot.
+    pub view_angle: f32,
+    /// How much less a visible neighbour counts towards alignment and cohesion at the edge of
+    /// `visible_range` than up close. 0 weights everyone equally.
+    pub distance_falloff: f32,
+
+    /// How far ahead the obstacle feelers reach.
+    pub obstacle_lookahead: f32,
+    pub obstacle_avoid_factor: f32,
+
+    /// Boids within this distance of a predator flee from it, whichever way they are facing.
+    pub fear_range: f32,
+    pub flee_factor: f32,
+    /// How far a `ChaseStrategy::Straggler` predator looks for prey.
+    pub predator_hunt_range: f32,
+    /// Whether predators despawn the boids they catch.
+    pub predator_kills: bool,
+    pub predator_kill_range: f32,
+    /// Keep the species `spawn_count` when a boid is killed so it gets respawned.
+    pub predator_respawn_kills: bool,
+
+    /// How hard boids are pulled towards the goal waypoint each tick.
+    pub goal_weight: f32,
+    /// How close the centre of the flock gets to a waypoint before moving on to the next.
+    pub waypoint_radius: f32,
 
     pub spatial_hash_size: u32,
 
+    /// Simulation ticks per second. The flocking factors are applied once per tick.
+    pub tick_rate: f64,
+
+    /// Whether `flock_metrics` computes the flock metrics at all.
+    pub metrics: bool,
+    /// Ticks between metric samples. Each sample queries the spatial index once per boid.
+    pub metrics_every: u32,
+
+    /// Seed for `BoidRng`; the same seed and configuration reproduce the same flock.
+    pub seed: u64,
+
     pub bounds_gizmo: BoidGizmoConfig,
     pub quadtree_gizmo: BoidGizmoConfig,
     pub protected_range_gizmo: BoidGizmoConfig,
     pub visible_range_gizmo: BoidGizmoConfig,
+    pub obstacle_gizmo: BoidGizmoConfig,
+    pub path_gizmo: BoidGizmoConfig,
 
     pub update_color_sample_rate: f32,
     pub update_color_type: ColorType,
@@ -33,7 +90,6 @@ impl Default for BoidConfiguration {
     fn default() -> Self {
         BoidConfiguration {
             total_boids: 0,
-            spawn_count: 100,
             spawn_range: Rect {
                 min: Vec2::new(-200.0, -200.0),
                 max: Vec2::new(200.0, 200.0),
@@ -45,23 +101,42 @@ impl Default for BoidConfiguration {
             },
 
             turn_factor: 1.2,
+            boundary_mode: BoundaryMode::SoftMargin,
 
-            visible_range: 100.0,
-            protected_range: 40.0,
+            species: vec![SpeciesConfig::default()],
+            interactions: vec![vec![Interaction::Align]],
+            neighbor_mode: NeighborMode::Metric,
+            view_angle: 360.0,
+            distance_falloff: 0.0,
 
-            centering_factor: 0.0005,
-            avoid_factor: 0.05,
-            matching_factor: 0.05,
+            obstacle_lookahead: 60.0,
+            obstacle_avoid_factor: 3.0,
 
-            max_speed: 100.0,
-            min_speed: 2.0,
+            fear_range: 120.0,
+            flee_factor: 4.0,
+            predator_hunt_range: 250.0,
+            predator_kills: false,
+            predator_kill_range: 8.0,
+            predator_respawn_kills: false,
+
+            goal_weight: 0.5,
+            waypoint_radius: 60.0,
 
             spatial_hash_size: 100,
 
+            tick_rate: 64.0,
+
+            metrics: true,
+            metrics_every: 8,
+
+            seed: 0,
+
             bounds_gizmo: BoidGizmoConfig::new(false, [0.8, 0.6, 0.8, 1.0]),
             quadtree_gizmo: BoidGizmoConfig::new(false, [0.0, 1.0, 0.0, 0.1]),
             protected_range_gizmo: BoidGizmoConfig::new(false, [1.0, 0.0, 0.0, 0.1]),
             visible_range_gizmo: BoidGizmoConfig::new(false, [0.6, 1.0, 0.0, 0.1]),
+            obstacle_gizmo: BoidGizmoConfig::new(true, [1.0, 0.6, 0.2, 1.0]),
+            path_gizmo: BoidGizmoConfig::new(true, [0.3, 0.6, 1.0, 0.8]),
 
             update_color_sample_rate: 0.15,
             update_color_type: ColorType::Initial,
@@ -69,7 +144,158 @@ impl Default for BoidConfiguration {
     }
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
+impl BoidConfiguration {
+    /// Total boids across all species.
+    pub fn spawn_count(&self) -> u32 {
+        self.species.iter().map(|species| species.spawn_count).sum()
+    }
+
+    /// How species `a` treats species `b`. Pairs missing from the matrix flock within a species
+    /// and ignore each other across species.
+    pub fn interaction(&self, a: usize, b: usize) -> Interaction {
+        self.interactions
+            .get(a)
+            .and_then(|row| row.get(b))
+            .copied()
+            .unwrap_or(if a == b {
+                Interaction::Align
+            } else {
+                Interaction::Ignore
+            })
+    }
+
+    /// The bounds to wrap neighbour queries around, if the boundary wraps.
+    pub fn wrap_bounds(&self) -> Option<Rect> {
+        (self.boundary_mode == BoundaryMode::Wrap).then_some(self.boid_bounds)
+    }
+
+    /// The largest neighbour lookup radius of any species.
+    pub fn max_range(&self) -> f32 {
+        self.species
+            .iter()
+            .map(|species| species.protected_range.max(species.visible_range))
+            .fold(0.0, f32::max)
+    }
+
+    pub fn add_species(&mut self, species: SpeciesConfig) {
+        self.species.push(species);
+        self.fill_interactions();
+    }
+
+    /// Pulls settings that files can carry, but the UI doesn't offer, back into a workable range.
+    /// Call it wherever a configuration comes from outside, along with `fill_interactions`.
+    pub fn enforce_limits(&mut self) {
+        self.spatial_hash_size = self.spatial_hash_size.max(MIN_SPATIAL_HASH_SIZE);
+        self.metrics_every = self.metrics_every.max(1);
+        self.tick_rate = if self.tick_rate.is_nan() {
+            BoidConfiguration::default().tick_rate
+        } else {
+            self.tick_rate.clamp(*TICK_RATES.start(), *TICK_RATES.end())
+        };
+    }
+
+    /// Makes the interaction matrix square with one row and column per species. Missing pairs take
+    /// the defaults from `interaction`.
+    pub fn fill_interactions(&mut self) {
+        let size = self.species.len();
+        self.interactions = (0..size)
+            .map(|a| (0..size).map(|b| self.interaction(a, b)).collect())
+            .collect();
+    }
+
+    /// Removes a species and its row and column of the interaction matrix. This doesn't touch the
+    /// boids; send `RemoveSpecies` to also renumber their `Species`.
+    pub fn remove_species(&mut self, index: usize) {
+        if index >= self.species.len() {
+            return;
+        }
+
+        self.species.remove(index);
+        if index < self.interactions.len() {
+            self.interactions.remove(index);
+        }
+        for row in self.interactions.iter_mut() {
+            if index < row.len() {
+                row.remove(index);
+            }
+        }
+    }
+}
+
+/// Rules for one kind of boid.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeciesConfig {
+    pub name: String,
+    pub spawn_count: u32,
+    pub visible_range: f32,
+    pub protected_range: f32,
+    pub avoid_factor: f32,
+    pub centering_factor: f32,
+    pub matching_factor: f32,
+    pub max_speed: f32,
+    pub min_speed: f32,
+    /// Each boid's initial color is this scaled by a random amount per channel.
+    pub color_rgb: [f32; 3],
+    pub shape: BoidShape,
+}
+
+impl Default for SpeciesConfig {
+    fn default() -> Self {
+        SpeciesConfig {
+            name: "boids".to_string(),
+            spawn_count: 100,
+
+            visible_range: 100.0,
+            protected_range: 40.0,
+
+            centering_factor: 0.0005,
+            avoid_factor: 0.05,
+            matching_factor: 0.05,
+
+            max_speed: 100.0,
+            min_speed: 2.0,
+
+            color_rgb: [0.0, 1.0, 1.0],
+            shape: BoidShape::Triangle,
+        }
+    }
+}
+
+/// How a boid treats a neighbour of another (or its own) species.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Interaction {
+    /// Separation, alignment and cohesion as usual.
+    Align,
+    /// Not a neighbour at all.
+    Ignore,
+    /// Kept out of the whole visible range, with no alignment or cohesion.
+    Avoid,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum BoidShape {
+    #[default]
+    Triangle,
+    Dart,
+    Circle,
+}
+
+impl BoundaryMode {
+    pub const ALL: [BoundaryMode; 5] = [
+        BoundaryMode::SoftMargin,
+        BoundaryMode::Wrap,
+        BoundaryMode::Reflect,
+        BoundaryMode::Circular,
+        BoundaryMode::None,
+    ];
+}
+
+impl BoidShape {
+    pub const ALL: [BoidShape; 3] = [BoidShape::Triangle, BoidShape::Dart, BoidShape::Circle];
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
 pub enum ColorType {
     Initial,
     Synthwave,
@@ -77,7 +303,44 @@ pub enum ColorType {
     PrimaryRGB,
 }
 
-#[derive(Default, Debug)]
+/// What happens at the edge of `boid_bounds`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum BoundaryMode {
+    /// Boids outside the bounds turn back by `turn_factor` each tick.
+    SoftMargin,
+    /// The bounds are a torus; leaving one side comes back in the other and neighbours are found
+    /// across the seam.
+    Wrap,
+    /// Boids bounce off the edges.
+    Reflect,
+    /// Like `SoftMargin`, for the largest circle that fits in the bounds.
+    Circular,
+    /// No bounds at all. Boids that drift past the area the spatial index covers stop finding
+    /// neighbours.
+    None,
+}
+
+/// Which other boids count as neighbours for alignment and cohesion.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum NeighborMode {
+    /// Every boid within `visible_range`.
+    Metric,
+    /// The `k` nearest boids, however far away they are.
+    Topological { k: usize },
+    /// The `k` nearest boids that are also within `visible_range`.
+    Hybrid { k: usize },
+}
+
+impl NeighborMode {
+    pub fn k(&self) -> Option<usize> {
+        match self {
+            NeighborMode::Metric => None,
+            NeighborMode::Topological { k } | NeighborMode::Hybrid { k } => Some(*k),
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
 pub struct BoidGizmoConfig {
     pub enabled: bool,
     pub color_rgba: [f32; 4],
@@ -0,0 +1,229 @@
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoadFailedEvent, AssetLoader, LoadContext};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_egui::egui;
+
+use crate::config::BoidConfiguration;
+use crate::preset::{self, Preset, PresetError};
+
+/// Loads a `BoidConfiguration` asset from RON, the same format presets are saved in.
+#[derive(Default)]
+pub struct ConfigLoader;
+
+impl AssetLoader for ConfigLoader {
+    type Asset = BoidConfiguration;
+    type Settings = ();
+    type Error = PresetError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = vec![];
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(PresetError::Io)?;
+        ron::de::from_bytes(&bytes).map_err(PresetError::Parse)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ron"]
+    }
+}
+
+/// The configuration file being watched. With the asset server watching for changes every save
+/// of the file is applied to the running simulation.
+#[derive(Resource, Default, Debug)]
+pub struct ConfigFile {
+    /// Asset path of the file, relative to the assets directory.
+    pub path: String,
+    pub handle: Option<Handle<BoidConfiguration>>,
+    /// Why the last load failed, cleared by the next good one.
+    pub error: Option<String>,
+    pub reloads: u32,
+}
+
+/// Replaces the tuning in `config` with the file's, like a preset, and keeps the running seed so
+/// an edit doesn't change what the next restart spawns.
+pub fn apply(config: &mut BoidConfiguration, file: BoidConfiguration) {
+    preset::apply(
+        config,
+        Preset {
+            config: file,
+            seed: None,
+        },
+    );
+}
+
+pub fn config_file_watch(asset_server: Res<AssetServer>, mut file: ResMut<ConfigFile>) {
+    if !file.path.is_empty() {
+        file.handle = Some(asset_server.load(file.path.clone()));
+    }
+}
+
+/// Applies the file whenever it is (re)loaded. Boids are left where they are; only a changed
+/// `spawn_count` adds or removes any, through `boid_ensure_count`.
+pub fn config_file_apply(
+    mut events: EventReader<AssetEvent<BoidConfiguration>>,
+    mut failures: EventReader<AssetLoadFailedEvent<BoidConfiguration>>,
+    assets: Res<Assets<BoidConfiguration>>,
+    mut file: ResMut<ConfigFile>,
+    mut config: Query<&mut BoidConfiguration>,
+) {
+    let Some(id) = file.handle.as_ref().map(|handle| handle.id()) else {
+        events.clear();
+        failures.clear();
+        return;
+    };
+
+    for failure in failures.read().filter(|failure| failure.id == id) {
+        file.error = Some(failure.error.to_string());
+    }
+
+    let changed = events.read().any(|event| match event {
+        AssetEvent::Added { id: changed } | AssetEvent::Modified { id: changed } => *changed == id,
+        _ => false,
+    });
+    if !changed {
+        return;
+    }
+
+    if let (Some(loaded), Ok(mut config)) = (assets.get(id), config.get_single_mut()) {
+        apply(&mut config, loaded.clone());
+        file.error = None;
+        file.reloads += 1;
+    }
+}
+
+/// The config file section of `boids_ui`.
+#[derive(SystemParam)]
+pub struct ConfigFileEditor<'w> {
+    asset_server: Res<'w, AssetServer>,
+    file: ResMut<'w, ConfigFile>,
+}
+
+impl ConfigFileEditor<'_> {
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.file.path)
+                .on_hover_text("relative to the assets directory");
+
+            if self.file.handle.is_none() {
+                if ui.button("watch").clicked() && !self.file.path.is_empty() {
+                    self.file.handle = Some(self.asset_server.load(self.file.path.clone()));
+                    self.file.reloads = 0;
+                }
+            } else if ui.button("stop").clicked() {
+                self.file.handle = None;
+                self.file.error = None;
+            }
+        });
+
+        if self.file.handle.is_some() {
+            ui.label(format!("applied {} times", self.file.reloads));
+        }
+
+        if let Some(error) = &self.file.error {
+            ui.colored_label(egui::Color32::LIGHT_RED, error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use bevy::prelude::*;
+
+    use crate::config::TICK_RATES;
+    use crate::config_file::ConfigFile;
+    use crate::{test_app, Boid, BoidConfiguration};
+
+    #[test]
+    fn applies_without_respawning() {
+        let mut app = test_app(20, None);
+        app.update();
+
+        let boids = |app: &mut App| -> Vec<Entity> {
+            let mut boids = app
+                .world_mut()
+                .query_filtered::<Entity, With<Boid>>()
+                .iter(app.world())
+                .collect::<Vec<_>>();
+            boids.sort();
+            boids
+        };
+        let before = boids(&mut app);
+        let seed = app
+            .world_mut()
+            .query::<&BoidConfiguration>()
+            .single(app.world())
+            .seed;
+
+        let mut edited = BoidConfiguration {
+            view_angle: 123.0,
+            seed: seed.wrapping_add(1),
+            ..default()
+        };
+        edited.species[0].spawn_count = 25;
+        let handle = app
+            .world_mut()
+            .resource_mut::<Assets<BoidConfiguration>>()
+            .add(edited);
+        app.world_mut().resource_mut::<ConfigFile>().handle = Some(handle);
+
+        for _ in 0..5 {
+            app.update();
+        }
+
+        let config = app
+            .world_mut()
+            .query::<&BoidConfiguration>()
+            .single(app.world())
+            .clone();
+        assert_eq!(config.view_angle, 123.0);
+        assert_eq!(config.seed, seed, "keeps the running seed");
+
+        let after = boids(&mut app);
+        assert_eq!(after.len(), 25);
+        assert!(
+            before.iter().all(|boid| after.contains(boid)),
+            "existing boids are kept"
+        );
+    }
+
+    #[test]
+    fn limits_the_tick_rate() {
+        let mut app = test_app(20, None);
+        app.update();
+
+        let file = BoidConfiguration {
+            tick_rate: 0.0,
+            ..default()
+        };
+        let handle = app
+            .world_mut()
+            .resource_mut::<Assets<BoidConfiguration>>()
+            .add(file);
+        app.world_mut().resource_mut::<ConfigFile>().handle = Some(handle);
+
+        // `Time::<Fixed>::from_hz(0.0)` would panic in here
+        for _ in 0..5 {
+            app.update();
+        }
+
+        let config = app
+            .world_mut()
+            .query::<&BoidConfiguration>()
+            .single(app.world())
+            .clone();
+        assert_eq!(config.tick_rate, *TICK_RATES.start());
+        assert_eq!(
+            app.world().resource::<Time<Fixed>>().timestep(),
+            Time::<Fixed>::from_hz(*TICK_RATES.start()).timestep()
+        );
+    }
+}
@@ -0,0 +1,471 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_egui::egui;
+
+use crate::boid::{Boid, BoidId, Neighbors};
+
+/// Starts every columnar export, followed by the version as a little endian `u32`.
+pub const COLUMNAR_MAGIC: &[u8; 4] = b"BCOL";
+pub const COLUMNAR_VERSION: u32 = 1;
+
+pub const CSV_HEADER: &str = "id,tick,x,y,vx,vy,speed,neighbors";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One row per boid per sample, with `CSV_HEADER` as the first line.
+    #[default]
+    Csv,
+    /// After the header, one row group per sample: the row count as a `u32`, then each column
+    /// in `CSV_HEADER` order as that many values. Ids and ticks are `u64`, neighbours `u32` and
+    /// the rest `f32`, all little endian.
+    Columnar,
+}
+
+impl ExportFormat {
+    /// `.csv` files are written as CSV, anything else as columnar.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("csv") => ExportFormat::Csv,
+            _ => ExportFormat::Columnar,
+        }
+    }
+}
+
+/// What to export and where.
+#[derive(Clone, Debug)]
+pub struct ExportSettings {
+    pub path: PathBuf,
+    pub format: ExportFormat,
+    /// Write a sample every this many ticks.
+    pub every: u32,
+}
+
+impl ExportSettings {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        ExportSettings {
+            format: ExportFormat::from_path(&path),
+            path,
+            every: 1,
+        }
+    }
+}
+
+/// One boid at one sampled tick.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrajectoryRow {
+    pub id: u64,
+    pub tick: u64,
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub neighbors: u32,
+}
+
+impl TrajectoryRow {
+    pub fn speed(&self) -> f32 {
+        self.velocity.length()
+    }
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(std::io::Error),
+    Format(String),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Io(error) => write!(f, "{}", error),
+            ExportError::Format(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+impl From<std::io::Error> for ExportError {
+    fn from(error: std::io::Error) -> Self {
+        ExportError::Io(error)
+    }
+}
+
+/// An open export file.
+pub struct TrajectoryWriter {
+    file: BufWriter<File>,
+    format: ExportFormat,
+}
+
+impl TrajectoryWriter {
+    pub fn create(path: &Path, format: ExportFormat) -> Result<Self, ExportError> {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut file = BufWriter::new(File::create(path)?);
+        match format {
+            ExportFormat::Csv => writeln!(file, "{}", CSV_HEADER)?,
+            ExportFormat::Columnar => {
+                file.write_all(COLUMNAR_MAGIC)?;
+                file.write_all(&COLUMNAR_VERSION.to_le_bytes())?;
+            }
+        }
+
+        Ok(TrajectoryWriter { file, format })
+    }
+
+    /// Writes one sample.
+    pub fn write(&mut self, rows: &[TrajectoryRow]) -> Result<(), ExportError> {
+        match self.format {
+            ExportFormat::Csv => {
+                for row in rows {
+                    writeln!(
+                        self.file,
+                        "{},{},{},{},{},{},{},{}",
+                        row.id,
+                        row.tick,
+                        row.position.x,
+                        row.position.y,
+                        row.velocity.x,
+                        row.velocity.y,
+                        row.speed(),
+                        row.neighbors
+                    )?;
+                }
+            }
+            ExportFormat::Columnar => {
+                let file = &mut self.file;
+                file.write_all(&(rows.len() as u32).to_le_bytes())?;
+
+                write_column(file, rows, |row| row.id.to_le_bytes())?;
+                write_column(file, rows, |row| row.tick.to_le_bytes())?;
+                write_column(file, rows, |row| row.position.x.to_le_bytes())?;
+                write_column(file, rows, |row| row.position.y.to_le_bytes())?;
+                write_column(file, rows, |row| row.velocity.x.to_le_bytes())?;
+                write_column(file, rows, |row| row.velocity.y.to_le_bytes())?;
+                write_column(file, rows, |row| row.speed().to_le_bytes())?;
+                write_column(file, rows, |row| row.neighbors.to_le_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), ExportError> {
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+fn write_column<const N: usize>(
+    file: &mut impl Write,
+    rows: &[TrajectoryRow],
+    value: impl Fn(&TrajectoryRow) -> [u8; N],
+) -> std::io::Result<()> {
+    for row in rows {
+        file.write_all(&value(row))?;
+    }
+    Ok(())
+}
+
+/// Reads a columnar export back into rows.
+pub fn read_columnar(mut reader: impl Read) -> Result<Vec<TrajectoryRow>, ExportError> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+
+    let Some(body) = bytes.strip_prefix(COLUMNAR_MAGIC) else {
+        return Err(ExportError::Format("not a columnar export".to_string()));
+    };
+
+    let mut cursor = body;
+    let mut take = |len: usize| -> Result<&[u8], ExportError> {
+        if cursor.len() < len {
+            return Err(ExportError::Format("truncated columnar export".to_string()));
+        }
+        let (taken, rest) = cursor.split_at(len);
+        cursor = rest;
+        Ok(taken)
+    };
+
+    let version = u32::from_le_bytes(take(4)?.try_into().unwrap());
+    if version != COLUMNAR_VERSION {
+        return Err(ExportError::Format(format!(
+            "columnar version {} is not supported, expected {}",
+            version, COLUMNAR_VERSION
+        )));
+    }
+
+    let mut rows = vec![];
+    while let Ok(count) = take(4) {
+        let count = u32::from_le_bytes(count.try_into().unwrap()) as usize;
+
+        let mut u64s = || -> Result<Vec<u64>, ExportError> {
+            Ok(take(count * 8)?
+                .chunks_exact(8)
+                .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+                .collect())
+        };
+        let ids = u64s()?;
+        let ticks = u64s()?;
+
+        let mut f32s = || -> Result<Vec<f32>, ExportError> {
+            Ok(take(count * 4)?
+                .chunks_exact(4)
+                .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+                .collect())
+        };
+        let (x, y, vx, vy, _speed) = (f32s()?, f32s()?, f32s()?, f32s()?, f32s()?);
+
+        let neighbors = take(count * 4)?
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect::<Vec<_>>();
+
+        for i in 0..count {
+            rows.push(TrajectoryRow {
+                id: ids[i],
+                tick: ticks[i],
+                position: Vec2::new(x[i], y[i]),
+                velocity: Vec2::new(vx[i], vy[i]),
+                neighbors: neighbors[i],
+            });
+        }
+    }
+
+    Ok(rows)
+}
+
+/// The running export. Ticks count from when it started.
+#[derive(Resource, Default)]
+pub struct TrajectoryExport {
+    pub settings: Option<ExportSettings>,
+    writer: Option<TrajectoryWriter>,
+    pub tick: u64,
+    pub samples: u64,
+    pub error: Option<String>,
+}
+
+impl TrajectoryExport {
+    pub fn is_running(&self) -> bool {
+        self.writer.is_some()
+    }
+
+    pub fn start(&mut self, settings: ExportSettings) {
+        self.stop();
+        match TrajectoryWriter::create(&settings.path, settings.format) {
+            Ok(writer) => {
+                self.writer = Some(writer);
+                self.error = None;
+            }
+            Err(error) => self.error = Some(error.to_string()),
+        }
+        self.settings = Some(settings);
+        self.tick = 0;
+        self.samples = 0;
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(writer) = self.writer.take() {
+            if let Err(error) = writer.finish() {
+                self.error = Some(error.to_string());
+            }
+        }
+    }
+}
+
+/// Writes a sample every `ExportSettings::every` ticks, ordered by `BoidId`.
+pub fn export_trajectories(
+    mut export: ResMut<TrajectoryExport>,
+    boids: Query<(&BoidId, &Boid, &Neighbors)>,
+) {
+    let export = &mut *export;
+    let (Some(writer), Some(settings)) = (export.writer.as_mut(), export.settings.as_ref()) else {
+        return;
+    };
+
+    let tick = export.tick;
+    export.tick += 1;
+    if !tick.is_multiple_of(settings.every.max(1) as u64) {
+        return;
+    }
+
+    let mut rows = boids
+        .iter()
+        .map(|(id, boid, neighbors)| TrajectoryRow {
+            id: id.0,
+            tick,
+            position: boid.position,
+            velocity: boid.velocity,
+            neighbors: neighbors.0,
+        })
+        .collect::<Vec<_>>();
+    rows.sort_by_key(|row| row.id);
+
+    match writer.write(&rows) {
+        Ok(()) => export.samples += 1,
+        Err(error) => {
+            export.error = Some(error.to_string());
+            export.writer = None;
+        }
+    }
+}
+
+pub struct ExportState {
+    path: String,
+    every: u32,
+}
+
+impl Default for ExportState {
+    fn default() -> Self {
+        ExportState {
+            path: "exports/trajectories.csv".to_string(),
+            every: 1,
+        }
+    }
+}
+
+/// The export section of `boids_ui`.
+#[derive(SystemParam)]
+pub struct ExportEditor<'w, 's> {
+    export: ResMut<'w, TrajectoryExport>,
+    state: Local<'s, ExportState>,
+}
+
+impl ExportEditor<'_, '_> {
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let state = &mut *self.state;
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut state.path)
+                .on_hover_text(".csv for CSV, anything else for columnar");
+            ui.label("every");
+            ui.add(egui::DragValue::new(&mut state.every).range(1..=10000));
+            ui.label("ticks");
+        });
+
+        ui.horizontal(|ui| {
+            if self.export.is_running() {
+                if ui.button("stop").clicked() {
+                    self.export.stop();
+                }
+            } else if ui.button("start").clicked() {
+                self.export.start(ExportSettings {
+                    every: state.every,
+                    ..ExportSettings::new(&state.path)
+                });
+            }
+
+            if let Some(settings) = &self.export.settings {
+                ui.label(format!(
+                    "{:?}, {} samples to {}",
+                    settings.format,
+                    self.export.samples,
+                    settings.path.display()
+                ));
+            }
+        });
+
+        if let Some(error) = &self.export.error {
+            ui.colored_label(egui::Color32::LIGHT_RED, error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use bevy::prelude::*;
+
+    use crate::boid::BoidId;
+    use crate::export::{
+        read_columnar, ExportFormat, ExportSettings, TrajectoryExport, CSV_HEADER,
+    };
+    use crate::{test_app_with, Boid, BoidsPlugin};
+
+    fn app(export: ExportSettings) -> App {
+        test_app_with(BoidsPlugin {
+            spawn_count: 20,
+            export: Some(export),
+            ..BoidsPlugin::headless()
+        })
+    }
+
+    #[test]
+    fn exports_columnar() {
+        let path = std::env::temp_dir().join(format!("rs-boids-{}.bcol", std::process::id()));
+        let mut app = app(ExportSettings {
+            every: 2,
+            ..ExportSettings::new(&path)
+        });
+        assert_eq!(ExportFormat::from_path(&path), ExportFormat::Columnar);
+
+        for _ in 0..30 {
+            app.update();
+        }
+        let mut export = app.world_mut().resource_mut::<TrajectoryExport>();
+        let samples = export.samples;
+        export.stop();
+
+        let rows = read_columnar(std::fs::File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(samples > 2);
+        assert_eq!(rows.len() as u64, samples * 20);
+        assert!(rows.iter().all(|row| row.tick % 2 == 0));
+
+        let first = rows.iter().take(20).map(|row| row.id).collect::<Vec<_>>();
+        assert_eq!(first, (0..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn exports_csv_with_fresh_ids() {
+        let path = std::env::temp_dir().join(format!("rs-boids-{}.csv", std::process::id()));
+        let mut app = app(ExportSettings::new(&path));
+
+        for _ in 0..5 {
+            app.update();
+        }
+
+        // drop half the flock; boid_ensure_count replaces them, likely in the freed entities
+        let half = app
+            .world_mut()
+            .query_filtered::<Entity, With<Boid>>()
+            .iter(app.world())
+            .take(10)
+            .collect::<Vec<_>>();
+        for entity in half {
+            app.world_mut().despawn(entity);
+        }
+        for _ in 0..5 {
+            app.update();
+        }
+
+        let mut ids = app
+            .world_mut()
+            .query::<&BoidId>()
+            .iter(app.world())
+            .map(|id| id.0)
+            .collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(ids.len(), 20);
+        assert_eq!(ids.iter().filter(|id| **id >= 20).count(), 10, "new ids");
+
+        app.world_mut().resource_mut::<TrajectoryExport>().stop();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut lines = text.lines();
+        assert_eq!(lines.next(), Some(CSV_HEADER));
+        let row = lines.next().unwrap().split(',').collect::<Vec<_>>();
+        assert_eq!(row.len(), 8);
+        assert_eq!(row[..2], ["0", "0"]);
+    }
+}
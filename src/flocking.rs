@@ -0,0 +1,396 @@
+use bevy::prelude::*;
+
+use crate::boid::{Boid, Neighbors, Species};
+use crate::boundary;
+use crate::config::{BoidConfiguration, Interaction, NeighborMode};
+use crate::highlight::{Highlighted, HighlightedNeighbor};
+use crate::perf::{IndexProfile, Span};
+use crate::predator::Predator;
+use crate::quadtree::Quadtree;
+use crate::spatial::SpatialIndex;
+use crate::spatial_hash::SpatialHash;
+use crate::uniform_grid::UniformGrid;
+
+#[derive(Resource, Deref, DerefMut)]
+pub struct QuadtreeJail(pub Quadtree<Entity, EntityWrapper>);
+
+impl SpatialIndex<EntityWrapper> for QuadtreeJail {
+    fn build(&mut self, points: impl IntoIterator<Item = (Vec2, EntityWrapper)>) {
+        self.0.build(points)
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn query_radius(
+        &self,
+        center: Vec2,
+        radius: f32,
+        visit: impl FnMut(Vec2, &EntityWrapper),
+    ) -> usize {
+        SpatialIndex::query_radius(&self.0, center, radius, visit)
+    }
+
+    fn k_nearest(&self, center: Vec2, k: usize) -> Vec<(Vec2, EntityWrapper)> {
+        self.0.k_nearest(center, k)
+    }
+
+    fn k_nearest_filtered(
+        &self,
+        center: Vec2,
+        k: usize,
+        wrap: Option<Rect>,
+        accept: impl FnMut(Vec2, &EntityWrapper) -> bool,
+        found: &mut Vec<(Vec2, EntityWrapper)>,
+    ) -> usize {
+        self.0.k_nearest_filtered(center, k, wrap, accept, found)
+    }
+}
+
+pub type BoidSpatialHash = SpatialHash<EntityWrapper>;
+
+pub type BoidUniformGrid = UniformGrid<EntityWrapper>;
+
+#[derive(States, Debug, Hash, Eq, PartialEq, Ord, PartialOrd, Clone)]
+pub enum SpatialState {
+    QuadTree,
+    SpatialHash,
+    UniformGrid,
+}
+
+#[derive(Clone, Debug)]
+pub struct EntityWrapper {
+    pub entity: Entity,
+    pub velocity: Vec2,
+    pub species: usize,
+    /// Predators share the index so boids can find them; they are not flockmates.
+    pub predator: bool,
+}
+
+// lets the quadtree key its points by entity
+impl From<&EntityWrapper> for Entity {
+    fn from(wrapper: &EntityWrapper) -> Entity {
+        wrapper.entity
+    }
+}
+
+// The spatial hash covers a larger area than the bounds so boids that overshoot still flock
+pub fn spatial_hash_bounds(config: &BoidConfiguration) -> Rect {
+    Rect::from_corners(config.boid_bounds.min * 12.0, config.boid_bounds.max * 12.0)
+}
+
+pub fn resize_spatial_hash(mut hash: ResMut<BoidSpatialHash>, config: Query<&BoidConfiguration>) {
+    let config = config.single();
+
+    let bounds = spatial_hash_bounds(config);
+    let cell_size = config.spatial_hash_size as f32;
+    if hash.bounds() != bounds || hash.cell_size() != cell_size {
+        hash.resize(bounds, cell_size);
+    }
+}
+
+pub fn resize_uniform_grid(mut grid: ResMut<BoidUniformGrid>, config: Query<&BoidConfiguration>) {
+    let config = config.single();
+
+    let bounds = spatial_hash_bounds(config);
+    let cell_size = config.spatial_hash_size as f32;
+    if grid.bounds() != bounds || grid.cell_size() != cell_size {
+        grid.resize(bounds, cell_size);
+    }
+}
+
+pub fn populate_index<I: SpatialIndex<EntityWrapper> + Resource>(
+    mut index: ResMut<I>,
+    boids: Query<(Entity, &Boid, &Species)>,
+    predators: Query<(Entity, &Predator)>,
+    mut profile: ResMut<IndexProfile>,
+) {
+    let span = Span::start();
+
+    let boids = boids.iter().map(|(entity, boid, species)| {
+        (
+            boid.position,
+            EntityWrapper {
+                entity,
+                velocity: boid.velocity,
+                species: species.0,
+                predator: false,
+            },
+        )
+    });
+    let predators = predators.iter().map(|(entity, predator)| {
+        (
+            predator.position,
+            EntityWrapper {
+                entity,
+                velocity: predator.velocity,
+                species: 0,
+                predator: true,
+            },
+        )
+    });
+
+    index.build(boids.chain(predators));
+
+    let (time, allocations) = span.end();
+    profile.build = time;
+    profile.allocations += allocations;
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn boid_flocking<I: SpatialIndex<EntityWrapper> + Resource>(
+    mut commands: Commands,
+    mut boids: Query<(
+        Entity,
+        &mut Boid,
+        &Species,
+        &mut Neighbors,
+        Option<&Highlighted>,
+    )>,
+    index: Res<I>,
+    config: Query<&BoidConfiguration>,
+    old_neighbors: Query<Entity, With<HighlightedNeighbor>>,
+    predators: Query<&Predator>,
+    mut profile: ResMut<IndexProfile>,
+    mut nearest: Local<Vec<(Vec2, EntityWrapper)>>,
+    mut avoids: Local<Vec<bool>>,
+) {
+    let span = Span::start();
+
+    for entity in old_neighbors.iter() {
+        commands.entity(entity).remove::<HighlightedNeighbor>();
+    }
+
+    let config = config.single();
+    let min_cos = view_cone_cos(config.view_angle);
+    let wrap = config.wrap_bounds();
+    let flee = !predators.is_empty() && config.flee_factor != 0.0 && config.fear_range > 0.0;
+    let mut candidates = 0;
+    let mut accepted = 0;
+
+    // in the k nearest modes species that are avoided need a search of their own
+    avoids.clear();
+    avoids.extend((0..config.species.len()).map(|a| {
+        (0..config.species.len()).any(|b| config.interaction(a, b) == Interaction::Avoid)
+    }));
+
+    for (entity, mut boid, species, mut neighbors, highlighted) in boids.iter_mut() {
+        let Some(rules) = config.species.get(species.0) else {
+            continue;
+        };
+        let max_range = rules.protected_range.max(rules.visible_range);
+
+        let position = boid.position;
+        let heading = boid.velocity.normalize_or_zero();
+
+        let mut dclose = Vec2::ZERO;
+
+        let mut visible_weight = 0.0;
+        let mut visible_count = 0;
+        let mut velocity_avg = Vec2::ZERO;
+        let mut position_avg = Vec2::ZERO;
+
+        let mut apply_rules = |other_position: Vec2, other_entity: &EntityWrapper| {
+            if entity == other_entity.entity || other_entity.predator {
+                return;
+            }
+
+            if !in_view(heading, other_position - position, min_cos) {
+                return;
+            }
+
+            let distance = position - other_position;
+            match config.interaction(species.0, other_entity.species) {
+                Interaction::Align => {}
+                Interaction::Ignore => return,
+                Interaction::Avoid => {
+                    if distance.length() <= rules.visible_range {
+                        dclose += distance;
+                    }
+                    return;
+                }
+            }
+
+            if distance.length() <= rules.protected_range {
+                dclose += distance;
+            }
+
+            let visible = match config.neighbor_mode {
+                NeighborMode::Metric | NeighborMode::Hybrid { .. } => {
+                    distance.length() <= rules.visible_range
+                }
+                NeighborMode::Topological { .. } => true,
+            };
+
+            if visible {
+                let weight = 1.0
+                    - config.distance_falloff
+                        * (distance.length() / rules.visible_range).clamp(0.0, 1.0);
+
+                visible_weight += weight;
+                visible_count += 1;
+                velocity_avg += other_entity.velocity * weight;
+
+                position_avg += other_position * weight;
+
+                if highlighted.is_some() {
+                    commands
+                        .entity(other_entity.entity)
+                        .insert(HighlightedNeighbor);
+                }
+            }
+        };
+
+        match config.neighbor_mode.k() {
+            None => {
+                candidates += index.query_radius_with_wrap(position, max_range, wrap, apply_rules);
+            }
+            Some(k) => {
+                // only boids that would count take up one of the k places
+                let hybrid = matches!(config.neighbor_mode, NeighborMode::Hybrid { .. });
+                candidates += index.k_nearest_filtered(
+                    position,
+                    k,
+                    wrap,
+                    |other_position, other_entity| {
+                        entity != other_entity.entity
+                            && !other_entity.predator
+                            && config.interaction(species.0, other_entity.species)
+                                == Interaction::Align
+                            && in_view(heading, other_position - position, min_cos)
+                            && (!hybrid || other_position.distance(position) <= rules.visible_range)
+                    },
+                    &mut nearest,
+                );
+                for (other_position, other_entity) in nearest.iter() {
+                    apply_rules(*other_position, other_entity);
+                }
+
+                if avoids[species.0] {
+                    candidates += index.query_radius_with_wrap(
+                        position,
+                        rules.visible_range,
+                        wrap,
+                        |other_position, other_entity| {
+                            if !other_entity.predator
+                                && config.interaction(species.0, other_entity.species)
+                                    == Interaction::Avoid
+                                && in_view(heading, other_position - position, min_cos)
+                            {
+                                dclose += position - other_position;
+                            }
+                        },
+                    );
+                }
+            }
+        }
+
+        neighbors.0 = visible_count;
+        accepted += visible_count as u64;
+        boid.velocity += dclose * rules.avoid_factor;
+
+        if flee {
+            // there are only ever a few predators, so look at each rather than search the index
+            let mut dflee = Vec2::ZERO;
+            for predator in predators.iter() {
+                let away = boundary::offset(wrap, predator.position, position);
+                if away.length() <= config.fear_range {
+                    dflee += away.normalize_or_zero() * (1.0 - away.length() / config.fear_range);
+                }
+            }
+            boid.velocity += dflee * config.flee_factor;
+        }
+
+        if visible_weight > 0.0 {
+            // alignment
+            velocity_avg /= visible_weight;
+            let velocity = boid.velocity;
+            boid.velocity += (velocity_avg - velocity) * rules.matching_factor;
+
+            // cohesion
+            position_avg /= visible_weight;
+            boid.velocity += (position_avg - position) * rules.centering_factor;
+        }
+    }
+
+    let (time, allocations) = span.end();
+    profile.query = time;
+    profile.candidates = candidates as u64;
+    profile.accepted = accepted;
+    profile.allocations += allocations;
+}
+
+/// Cosine of half the view cone, for comparing against the dot product in `in_view`.
+pub fn view_cone_cos(view_angle: f32) -> f32 {
+    (view_angle.clamp(0.0, 360.0).to_radians() / 2.0).cos()
+}
+
+/// Whether `offset` lies inside the view cone around `heading`. A boid with no heading sees
+/// everything.
+pub fn in_view(heading: Vec2, offset: Vec2, min_cos: f32) -> bool {
+    if heading == Vec2::ZERO || min_cos <= -1.0 {
+        return true;
+    }
+
+    heading.dot(offset.normalize_or_zero()) >= min_cos
+}
+
+#[cfg(test)]
+mod test {
+
+    use bevy::prelude::*;
+
+    use crate::flocking::{in_view, view_cone_cos};
+    use crate::{test_app, BoidConfiguration, NeighborMode, Neighbors, SpeciesConfig};
+
+    #[test]
+    fn view_cone() {
+        let heading = Vec2::Y;
+
+        let full = view_cone_cos(360.0);
+        assert!(in_view(heading, Vec2::NEG_Y, full), "no blind spot");
+
+        let forward = view_cone_cos(180.0);
+        assert!(in_view(heading, Vec2::new(1.0, 0.1), forward));
+        assert!(!in_view(heading, Vec2::new(1.0, -0.1), forward));
+
+        let narrow = view_cone_cos(90.0);
+        assert!(in_view(heading, Vec2::new(0.9, 1.0), narrow));
+        assert!(!in_view(heading, Vec2::new(1.1, 1.0), narrow));
+
+        assert!(in_view(Vec2::ZERO, Vec2::NEG_Y, narrow), "no heading");
+    }
+
+    #[test]
+    fn topological_finds_k_neighbours() {
+        let mut app = test_app(40, Some(9));
+        app.update();
+
+        // a second species the first ignores, mixed in with it
+        let world = app.world_mut();
+        let mut config = world.query::<&mut BoidConfiguration>().single_mut(world);
+        config.neighbor_mode = NeighborMode::Topological { k: 5 };
+        config.add_species(SpeciesConfig {
+            spawn_count: 40,
+            ..default()
+        });
+
+        for _ in 0..3 {
+            app.update();
+        }
+
+        let world = app.world_mut();
+        let neighbors = world
+            .query::<&Neighbors>()
+            .iter(world)
+            .map(|neighbors| neighbors.0)
+            .collect::<Vec<_>>();
+        assert_eq!(neighbors.len(), 80);
+        assert!(
+            neighbors.iter().all(|count| *count == 5),
+            "other species and self don't take places: {:?}",
+            neighbors
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use crate::boid::{Boid, Species};
+use crate::config::{BoidConfiguration, SpeciesConfig};
+use crate::flocking::spatial_hash_bounds;
+use crate::spatial_hash::find_cell_position;
+use crate::tool::{MouseTool, ToolMode};
+
+#[derive(Component)]
+pub struct Highlighted;
+
+#[derive(Component)]
+pub struct HighlightedNeighbor;
+
+pub fn boid_select_randomly(
+    mut commands: Commands,
+    boids: Query<(Entity, &Boid), Without<Highlighted>>,
+    highlighted: Query<Entity, With<Highlighted>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    q_windows: Query<&bevy::window::Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+    tool: Res<MouseTool>,
+) {
+    if tool.mode != ToolMode::Select {
+        return;
+    }
+
+    if mouse.just_pressed(MouseButton::Right) {
+        for entity in highlighted.iter() {
+            commands.entity(entity).remove::<Highlighted>();
+        }
+
+        return;
+    }
+
+    if mouse.just_pressed(MouseButton::Left) {
+        for entity in highlighted.iter() {
+            commands.entity(entity).remove::<Highlighted>();
+        }
+
+        let (camera, camera_transform) = camera.single();
+        if let Some(mouse) = cursor_world_position(q_windows.single(), camera, camera_transform) {
+            if let Some((_, entity)) = boids
+                .iter()
+                .map(|(entity, boid)| (boid.position.distance(mouse), entity))
+                .min_by(|(a, _), (b, _)| a.total_cmp(b))
+            {
+                commands.entity(entity).insert(Highlighted);
+            }
+        }
+    }
+}
+
+/// Where the cursor is in the world, if it is over the window.
+pub fn cursor_world_position(
+    window: &Window,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<Vec2> {
+    let mouse = window.cursor_position()?;
+    let mouse_ray = camera.viewport_to_world(camera_transform, mouse).ok()?;
+    Some(mouse_ray.origin.xy())
+}
+
+pub fn highlight_boid(
+    highlighted: Query<(&Boid, &Species), With<Highlighted>>,
+    config: Query<&BoidConfiguration>,
+    mut gizmos: Gizmos,
+) {
+    let config = config.single();
+
+    let bounds = spatial_hash_bounds(config);
+
+    let size = config.spatial_hash_size as f32;
+    let half_size = size / 2.0;
+    let x_cells = (bounds.width() / size).ceil() as u32;
+    let y_cells = (bounds.height() / size).ceil() as u32;
+    let cell_size = UVec2::new(x_cells, y_cells);
+
+    for (boid, species) in highlighted.iter() {
+        let Some(species) = config.species.get(species.0) else {
+            continue;
+        };
+
+        draw_view_cone(&mut gizmos, boid, species, config);
+
+        let radius = species.protected_range.max(species.visible_range);
+        let neighbors = (radius / size).ceil() as u32 + 1;
+
+        if let Some(cell) = find_cell_position(boid.position, bounds, size) {
+            for y in (cell.y - neighbors).clamp(0, cell_size.y)
+                ..(cell.y + neighbors).clamp(0, cell_size.y)
+            {
+                for x in (cell.x - neighbors).clamp(0, cell_size.x)
+                    ..(cell.x + neighbors).clamp(0, cell_size.x)
+                {
+                    gizmos.rect_2d(
+                        Isometry2d::from_translation(
+                            bounds.min + half_size + Vec2::new(x as f32, y as f32) * size,
+                        ),
+                        Vec2::splat(size),
+                        Color::srgba(1.0, 0.0, 0.0, 0.1),
+                    );
+                }
+            }
+        }
+    }
+}
+
+// The visible range, cut down to the view cone when there is a blind spot
+fn draw_view_cone(
+    gizmos: &mut Gizmos,
+    boid: &Boid,
+    species: &SpeciesConfig,
+    config: &BoidConfiguration,
+) {
+    let color = Color::srgb(0.0, 1.0, 0.0);
+    let half_angle = config.view_angle.clamp(0.0, 360.0).to_radians() / 2.0;
+
+    if half_angle >= PI || boid.velocity == Vec2::ZERO {
+        gizmos.circle_2d(boid.position, species.visible_range, color);
+        return;
+    }
+
+    let heading = boid.velocity.to_angle();
+
+    // arcs start at +Y and sweep counter-clockwise
+    gizmos.arc_2d(
+        Isometry2d::new(
+            boid.position,
+            Rot2::radians(heading - half_angle - FRAC_PI_2),
+        ),
+        half_angle * 2.0,
+        species.visible_range,
+        color,
+    );
+
+    for edge in [heading - half_angle, heading + half_angle] {
+        gizmos.line_2d(
+            boid.position,
+            boid.position + Vec2::from_angle(edge) * species.visible_range,
+            color,
+        );
+    }
+}
+
+pub fn boid_highlight_neighbors(
+    neighbor_boids: Query<&MeshMaterial2d<ColorMaterial>, (With<Boid>, Added<HighlightedNeighbor>)>,
+    boids: Query<(&MeshMaterial2d<ColorMaterial>, &Boid), Without<HighlightedNeighbor>>,
+    mut removed_neighbors: RemovedComponents<HighlightedNeighbor>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for entity in removed_neighbors.read() {
+        if let Ok((material_handle, boid)) = boids.get(entity) {
+            if let Some(color) = materials.get_mut(material_handle) {
+                color.color = boid.initial_color;
+            }
+        }
+    }
+
+    for material_handle in neighbor_boids.iter() {
+        if let Some(color) = materials.get_mut(material_handle) {
+            color.color = Color::srgba(1.0, 0.0, 0.0, 1.0);
+        }
+    }
+}
@@ -0,0 +1,577 @@
+use bevy::app::{RunFixedMainLoop, RunFixedMainLoopSystem};
+use bevy::diagnostic::FrameTimeDiagnosticsPlugin;
+use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
+use bevy_egui::EguiPlugin;
+
+pub mod boid;
+pub mod boundary;
+pub mod config;
+pub mod config_file;
+pub mod export;
+pub mod flocking;
+pub mod highlight;
+pub mod metrics;
+pub mod obstacle;
+pub mod path;
+pub mod perf;
+pub mod plots;
+pub mod predator;
+pub mod preset;
+pub mod quadtree;
+pub mod range_gizmos;
+pub mod render;
+pub mod replay;
+pub mod rng;
+pub mod snapshot;
+pub mod spatial;
+pub mod spatial_hash;
+pub mod time;
+pub mod tool;
+pub mod ui;
+pub mod uniform_grid;
+
+pub use boid::{Boid, BoidId, Neighbors, Species};
+pub use config::{
+    BoidConfiguration, BoidGizmoConfig, BoidShape, BoundaryMode, ColorType, Interaction,
+    NeighborMode, SpeciesConfig,
+};
+pub use config_file::ConfigFile;
+pub use export::{ExportFormat, ExportSettings, TrajectoryExport};
+pub use flocking::{BoidSpatialHash, BoidUniformGrid, EntityWrapper, QuadtreeJail, SpatialState};
+pub use obstacle::{Obstacle, ObstacleShape};
+pub use path::{Goal, PathFollow, PathMode, Waypoint};
+pub use perf::{CountingAllocator, IndexComparison, IndexProfile};
+pub use predator::{ChaseStrategy, Predator};
+pub use quadtree::Quadtree;
+pub use replay::{Recorder, Recording, Replay};
+pub use rng::BoidRng;
+pub use snapshot::{Snapshot, SnapshotFormat};
+pub use spatial::SpatialIndex;
+pub use spatial_hash::SpatialHash;
+pub use tool::{MouseTool, ToolMode};
+pub use uniform_grid::UniformGrid;
+
+/// System sets that make up the boid pipeline.
+///
+/// `Spawn`, `Flocking` and `Movement` run in that order in `FixedUpdate` at
+/// `BoidConfiguration::tick_rate`. `Ui`, `Select` and `Render` run in that order in `Update`.
+#[derive(SystemSet, Debug, Hash, Eq, PartialEq, Clone)]
+pub enum BoidSet {
+    /// The egui configuration window and the time controls.
+    Ui,
+    /// Spawning and despawning boids to match each species' `spawn_count`.
+    Spawn,
+    /// Reading the mouse for the current `MouseTool`.
+    Select,
+    /// Neighbour lookups and the separation/alignment/cohesion rules.
+    Flocking,
+    /// Bounds, speed limits and integrating velocity into position.
+    Movement,
+    /// Transforms, colors and gizmos.
+    Render,
+}
+
+/// Adds the boid simulation to an app.
+///
+/// With rendering enabled this expects `DefaultPlugins` (or equivalent) to be added first and a
+/// camera to be spawned by the host app. In headless mode only the simulation systems are added,
+/// so it runs on top of `MinimalPlugins`.
+pub struct BoidsPlugin {
+    pub spatial_state: SpatialState,
+    pub spawn_count: u32,
+    /// The configuration to start from. `spawn_count`, `bounds`, `seed` and `metrics` are applied
+    /// on top.
+    pub config: BoidConfiguration,
+    pub quadtree_bounds: Rect,
+    /// Points a quadtree leaf holds before it splits.
+    pub quadtree_capacity: usize,
+    pub quadtree_max_depth: usize,
+    /// Overrides the simulation bounds. When `None` the bounds are sized from the primary window,
+    /// or taken from `config` if there is no window.
+    pub bounds: Option<Rect>,
+    /// Seed for `BoidRng`. When `None` a random seed is picked at startup.
+    pub seed: Option<u64>,
+    /// Asset path of a configuration file to apply now and whenever it changes on disk. Needs
+    /// `AssetPlugin`.
+    pub config_file: Option<String>,
+    /// Start exporting trajectories as soon as the simulation runs.
+    pub export: Option<ExportSettings>,
+    /// Start with `BoidConfiguration::metrics` on. Off in `headless()`, where nothing shows them.
+    pub metrics: bool,
+    /// Skip the UI, gizmo and material systems.
+    pub headless: bool,
+}
+
+impl BoidsPlugin {
+    pub fn headless() -> Self {
+        BoidsPlugin {
+            metrics: false,
+            headless: true,
+            ..default()
+        }
+    }
+}
+
+impl Default for BoidsPlugin {
+    fn default() -> Self {
+        BoidsPlugin {
+            spatial_state: SpatialState::SpatialHash,
+            spawn_count: BoidConfiguration::default().spawn_count(),
+            config: BoidConfiguration::default(),
+            quadtree_bounds: Rect::new(-10000.0, -10000.0, 10000.0, 10000.0),
+            quadtree_capacity: 4,
+            quadtree_max_depth: quadtree::DEFAULT_MAX_DEPTH,
+            bounds: None,
+            seed: None,
+            config_file: None,
+            export: None,
+            metrics: true,
+            headless: false,
+        }
+    }
+}
+
+impl Plugin for BoidsPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<StatesPlugin>() {
+            app.add_plugins(StatesPlugin);
+        }
+
+        // `boid::setup` limits the config it spawns the same way
+        let mut limited = self.config.clone();
+        limited.enforce_limits();
+
+        app.insert_state(self.spatial_state.clone())
+            .add_event::<rng::RestartSimulation>()
+            .add_event::<boid::RemoveSpecies>()
+            .add_event::<time::StepSimulation>()
+            .add_event::<snapshot::RestoreSnapshot>()
+            .init_resource::<PathFollow>()
+            .init_resource::<MouseTool>()
+            .init_resource::<boid::NextBoidId>()
+            .init_resource::<Recorder>()
+            .init_resource::<Replay>()
+            .init_resource::<perf::FrameTimer>()
+            .init_resource::<perf::IndexProfile>()
+            .init_resource::<perf::IndexComparison>()
+            .insert_resource(QuadtreeJail(
+                Quadtree::new(self.quadtree_bounds, self.quadtree_capacity)
+                    .with_max_depth(self.quadtree_max_depth),
+            ))
+            .insert_resource(BoidSpatialHash::new(
+                Rect::default(),
+                limited.spatial_hash_size as f32,
+            ))
+            .insert_resource(BoidUniformGrid::new(
+                Rect::default(),
+                limited.spatial_hash_size as f32,
+            ))
+            .insert_resource(Time::<Fixed>::from_hz(limited.tick_rate))
+            .configure_sets(
+                Update,
+                (BoidSet::Ui, BoidSet::Select, BoidSet::Render).chain(),
+            )
+            .configure_sets(
+                FixedUpdate,
+                (BoidSet::Spawn, BoidSet::Flocking, BoidSet::Movement)
+                    .chain()
+                    .run_if(replay::not_replaying),
+            )
+            .add_systems(
+                Startup,
+                (
+                    boid::setup(
+                        BoidConfiguration {
+                            metrics: self.metrics,
+                            ..self.config.clone()
+                        },
+                        self.spawn_count,
+                        self.bounds,
+                        self.seed,
+                    ),
+                    boid::spawn_initial,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    rng::boid_restart,
+                    snapshot::snapshot_restore,
+                    boid::boid_remove_species,
+                    boid::boid_ensure_count,
+                    tool::tool_spawn_erase,
+                    boid::boid_assign_ids,
+                )
+                    .chain()
+                    .in_set(BoidSet::Spawn),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    (
+                        perf::profile_index_start,
+                        flocking::populate_index::<QuadtreeJail>,
+                        flocking::boid_flocking::<QuadtreeJail>,
+                        perf::profile_index_end,
+                        predator::predator_chase::<QuadtreeJail>,
+                        metrics::flock_metrics::<QuadtreeJail>,
+                    )
+                        .chain()
+                        .run_if(in_state(SpatialState::QuadTree)),
+                    (
+                        flocking::resize_spatial_hash,
+                        perf::profile_index_start,
+                        flocking::populate_index::<BoidSpatialHash>,
+                        flocking::boid_flocking::<BoidSpatialHash>,
+                        perf::profile_index_end,
+                        predator::predator_chase::<BoidSpatialHash>,
+                        metrics::flock_metrics::<BoidSpatialHash>,
+                    )
+                        .chain()
+                        .run_if(in_state(SpatialState::SpatialHash)),
+                    (
+                        flocking::resize_uniform_grid,
+                        perf::profile_index_start,
+                        flocking::populate_index::<BoidUniformGrid>,
+                        flocking::boid_flocking::<BoidUniformGrid>,
+                        perf::profile_index_end,
+                        predator::predator_chase::<BoidUniformGrid>,
+                        metrics::flock_metrics::<BoidUniformGrid>,
+                    )
+                        .chain()
+                        .run_if(in_state(SpatialState::UniformGrid)),
+                )
+                    .in_set(BoidSet::Flocking),
+            )
+            .add_systems(FixedUpdate, path::path_advance.in_set(BoidSet::Flocking))
+            .add_systems(
+                FixedUpdate,
+                (
+                    path::boid_seek_goal,
+                    tool::tool_apply_force,
+                    obstacle::boid_avoid_obstacles,
+                    boid::boid_turn_factor,
+                    boid::boid_speed_up,
+                    boid::boid_movement,
+                    boid::boid_confine,
+                    predator::predator_movement,
+                )
+                    .chain()
+                    .in_set(BoidSet::Movement),
+            )
+            .add_systems(
+                FixedUpdate,
+                (replay::recorder_capture, export::export_trajectories)
+                    .after(BoidSet::Movement)
+                    .run_if(replay::not_replaying),
+            )
+            .add_systems(
+                Update,
+                (replay::replay_advance, replay::replay_apply)
+                    .chain()
+                    .after(BoidSet::Select)
+                    .before(BoidSet::Render),
+            )
+            .add_systems(
+                Update,
+                (time::sync_tick_rate, time::boid_single_step)
+                    .chain()
+                    .after(BoidSet::Ui)
+                    .before(BoidSet::Select),
+            )
+            .add_systems(
+                Update,
+                (
+                    render::boid_rotation,
+                    render::update_boids_transform,
+                    render::update_predators_transform,
+                )
+                    .in_set(BoidSet::Render),
+            )
+            .add_systems(
+                RunFixedMainLoop,
+                (
+                    perf::perf_simulation_start.in_set(RunFixedMainLoopSystem::BeforeFixedMainLoop),
+                    perf::perf_simulation_end.in_set(RunFixedMainLoopSystem::AfterFixedMainLoop),
+                ),
+            )
+            .add_systems(Update, perf::perf_update_start.before(BoidSet::Ui))
+            .add_systems(Update, perf::perf_update_end.after(BoidSet::Render));
+
+        metrics::register(app);
+        perf::register(app);
+
+        let mut export = TrajectoryExport::default();
+        if let Some(settings) = &self.export {
+            export.start(settings.clone());
+            if let Some(error) = &export.error {
+                error!("could not start the export: {}", error);
+            }
+        }
+        app.insert_resource(export);
+
+        app.insert_resource(ConfigFile {
+            path: self.config_file.clone().unwrap_or_default(),
+            ..default()
+        });
+
+        // there is no file to watch in the browser
+        #[cfg(not(target_arch = "wasm32"))]
+        if app.is_plugin_added::<AssetPlugin>() {
+            app.init_asset::<BoidConfiguration>()
+                .init_asset_loader::<config_file::ConfigLoader>()
+                .add_systems(Startup, config_file::config_file_watch)
+                .add_systems(Update, config_file::config_file_apply.before(BoidSet::Ui));
+        }
+
+        if self.headless {
+            return;
+        }
+
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+
+        if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+            app.add_plugins(FrameTimeDiagnosticsPlugin);
+        }
+
+        app.init_resource::<plots::PlotHistory>()
+            .add_systems(Startup, render::setup_visuals)
+            .add_systems(
+                Update,
+                (
+                    ui::boids_ui,
+                    ui::time_ui,
+                    tool::tool_ui,
+                    replay::timeline_ui,
+                    (plots::plots_sample, plots::plots_ui).chain(),
+                )
+                    .in_set(BoidSet::Ui),
+            )
+            .add_systems(
+                Update,
+                (
+                    tool::tool_track_cursor,
+                    highlight::boid_select_randomly,
+                    path::path_edit_waypoints.after(tool::tool_track_cursor),
+                )
+                    .in_set(BoidSet::Select),
+            )
+            .add_systems(
+                Update,
+                (
+                    render::boid_attach_visuals,
+                    render::predator_attach_visuals,
+                    render::render_bounds_gizmo,
+                    obstacle::render_obstacles_gizmo,
+                    path::render_path_gizmo,
+                    tool::render_tool_gizmo,
+                    replay::render_replay_input_gizmo,
+                    quadtree::gizmos::render_quadtree,
+                    highlight::highlight_boid,
+                    highlight::boid_highlight_neighbors,
+                    range_gizmos::boid_draw_range_gizmos,
+                    render::boid_update_colors,
+                )
+                    .in_set(BoidSet::Render),
+            );
+    }
+}
+
+/// A headless app that advances 16ms per update, for tests that run the simulation.
+#[cfg(test)]
+pub(crate) fn test_app(spawn_count: u32, seed: Option<u64>) -> App {
+    test_app_with(BoidsPlugin {
+        spawn_count,
+        seed,
+        ..BoidsPlugin::headless()
+    })
+}
+
+/// `test_app` with the plugin set up by the caller. `AssetPlugin` is there for config files, but
+/// without watching `assets/` for changes.
+#[cfg(test)]
+pub(crate) fn test_app_with(plugin: BoidsPlugin) -> App {
+    let assets = AssetPlugin {
+        watch_for_changes_override: Some(false),
+        ..default()
+    };
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, assets))
+        .insert_resource(bevy::time::TimeUpdateStrategy::ManualDuration(
+            bevy::utils::Duration::from_millis(16),
+        ))
+        .add_plugins(plugin);
+    app
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::prelude::*;
+
+    use crate::boid::RemoveSpecies;
+    use crate::predator::{spawn_predator, ChaseStrategy};
+    use crate::time::StepSimulation;
+    use crate::{
+        test_app, test_app_with, Boid, BoidConfiguration, BoidsPlugin, SpatialState, Species,
+        SpeciesConfig,
+    };
+
+    fn run_seeded(seed: u64) -> Vec<(Vec2, Vec2)> {
+        let mut app = test_app(100, Some(seed));
+
+        for _ in 0..30 {
+            app.update();
+        }
+
+        app.world_mut()
+            .query::<&Boid>()
+            .iter(app.world())
+            .map(|boid| (boid.position, boid.velocity))
+            .collect()
+    }
+
+    #[test]
+    fn runs_headless() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins).add_plugins(BoidsPlugin {
+            spawn_count: 50,
+            ..BoidsPlugin::headless()
+        });
+
+        for _ in 0..10 {
+            app.update();
+        }
+
+        let count = app.world_mut().query::<&Boid>().iter(app.world()).count();
+        assert_eq!(count, 50, "should keep spawn_count boids");
+    }
+
+    #[test]
+    fn runs_each_spatial_index() {
+        for spatial_state in [
+            SpatialState::QuadTree,
+            SpatialState::SpatialHash,
+            SpatialState::UniformGrid,
+        ] {
+            let mut app = test_app_with(BoidsPlugin {
+                spawn_count: 200,
+                spatial_state: spatial_state.clone(),
+                ..BoidsPlugin::headless()
+            });
+
+            for _ in 0..20 {
+                app.update();
+            }
+
+            let count = app.world_mut().query::<&Boid>().iter(app.world()).count();
+            assert_eq!(count, 200, "{:?}", spatial_state);
+        }
+    }
+
+    #[test]
+    fn steps_while_paused() {
+        let mut app = test_app(10, None);
+        app.update();
+        app.world_mut().resource_mut::<Time<Virtual>>().pause();
+
+        let positions = |app: &mut App| -> Vec<Vec2> {
+            app.world_mut()
+                .query::<&Boid>()
+                .iter(app.world())
+                .map(|boid| boid.position)
+                .collect()
+        };
+
+        let before = positions(&mut app);
+        app.update();
+        assert_eq!(before, positions(&mut app), "paused should not move");
+
+        app.world_mut().send_event(StepSimulation);
+        app.update();
+        assert_ne!(before, positions(&mut app), "step should move");
+    }
+
+    #[test]
+    fn same_seed_same_flock() {
+        assert_eq!(run_seeded(7), run_seeded(7), "same seed should reproduce");
+        assert_ne!(
+            run_seeded(7),
+            run_seeded(8),
+            "different seeds should differ"
+        );
+    }
+
+    #[test]
+    fn predator_kills_lower_spawn_count() {
+        let mut app = test_app(50, None);
+        app.update();
+
+        let world = app.world_mut();
+        let mut config = world.query::<&mut BoidConfiguration>().single_mut(world);
+        config.predator_kills = true;
+        config.predator_kill_range = 10000.0;
+        spawn_predator(&mut world.commands(), Vec2::ZERO, ChaseStrategy::Nearest);
+
+        for _ in 0..5 {
+            app.update();
+        }
+
+        let world = app.world_mut();
+        let count = world.query::<&Boid>().iter(world).count();
+        let spawn_count = world
+            .query::<&BoidConfiguration>()
+            .single(world)
+            .spawn_count();
+        assert_eq!(count, 0, "everything is in reach");
+        assert_eq!(spawn_count, 0, "kills should not be respawned");
+    }
+
+    #[test]
+    fn keeps_count_per_species() {
+        let mut app = test_app(20, None);
+        app.update();
+
+        let counts = |app: &mut App| -> Vec<usize> {
+            let world = app.world_mut();
+            let species = world
+                .query::<&Species>()
+                .iter(world)
+                .map(|species| species.0)
+                .collect::<Vec<_>>();
+            (0..3)
+                .map(|index| species.iter().filter(|s| **s == index).count())
+                .collect()
+        };
+
+        let world = app.world_mut();
+        let mut config = world.query::<&mut BoidConfiguration>().single_mut(world);
+        config.add_species(SpeciesConfig {
+            spawn_count: 30,
+            ..default()
+        });
+        assert_eq!(config.interactions.len(), 2);
+        app.update();
+        assert_eq!(counts(&mut app), vec![20, 30, 0]);
+
+        app.world_mut().send_event(RemoveSpecies(0));
+        app.update();
+        assert_eq!(
+            counts(&mut app),
+            vec![30, 0, 0],
+            "the second species moves up"
+        );
+        assert_eq!(
+            app.world_mut()
+                .query::<&BoidConfiguration>()
+                .single(app.world())
+                .species
+                .len(),
+            1
+        );
+    }
+}
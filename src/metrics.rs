@@ -0,0 +1,346 @@
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::boid::{Boid, Species};
+use crate::boundary;
+use crate::config::BoidConfiguration;
+use crate::flocking::EntityWrapper;
+use crate::spatial::SpatialIndex;
+
+/// How aligned the flock is: the length of the mean heading, from 0 (every which way) to 1 (all
+/// the same way).
+pub const POLARIZATION: DiagnosticPath = DiagnosticPath::const_new("boids/polarization");
+/// How much the flock circles its centre: the mean angular momentum of the unit headings about
+/// the centroid, from 0 to 1.
+pub const MILLING: DiagnosticPath = DiagnosticPath::const_new("boids/milling");
+/// Mean distance from each boid to its nearest boid, over the boids with another in
+/// `visible_range`.
+pub const NEAREST_NEIGHBOR: DiagnosticPath = DiagnosticPath::const_new("boids/nearest_neighbor");
+pub const SPEED_MEAN: DiagnosticPath = DiagnosticPath::const_new("boids/speed_mean");
+pub const SPEED_STD_DEV: DiagnosticPath = DiagnosticPath::const_new("boids/speed_std_dev");
+pub const SPEED_MIN: DiagnosticPath = DiagnosticPath::const_new("boids/speed_min");
+pub const SPEED_MAX: DiagnosticPath = DiagnosticPath::const_new("boids/speed_max");
+/// Groups of boids connected by being within `visible_range` of one another.
+pub const CLUSTERS: DiagnosticPath = DiagnosticPath::const_new("boids/clusters");
+
+/// Every metric with the label `boids_ui` shows it under.
+pub const ALL: [(DiagnosticPath, &str); 8] = [
+    (POLARIZATION, "polarization"),
+    (MILLING, "milling"),
+    (NEAREST_NEIGHBOR, "nearest neighbour"),
+    (SPEED_MEAN, "speed mean"),
+    (SPEED_STD_DEV, "speed std dev"),
+    (SPEED_MIN, "speed min"),
+    (SPEED_MAX, "speed max"),
+    (CLUSTERS, "clusters"),
+];
+
+pub fn register(app: &mut App) {
+    for (path, _) in ALL {
+        let diagnostic = Diagnostic::new(path.clone());
+        // a smoothed cluster count is not a count
+        app.register_diagnostic(if path == CLUSTERS {
+            diagnostic.with_smoothing_factor(0.0)
+        } else {
+            diagnostic
+        });
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FlockMetrics {
+    pub polarization: f32,
+    pub milling: f32,
+    pub nearest_neighbor: f32,
+    pub speed_mean: f32,
+    pub speed_std_dev: f32,
+    pub speed_min: f32,
+    pub speed_max: f32,
+    pub clusters: usize,
+}
+
+/// A boid as `compute` sees it.
+pub struct MetricBoid {
+    pub entity: Entity,
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub species: usize,
+}
+
+fn find(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// Metrics for `boids`, which must be the boids in `index`. Predators in the index are skipped.
+pub fn compute<I: SpatialIndex<EntityWrapper>>(
+    boids: &[MetricBoid],
+    index: &I,
+    config: &BoidConfiguration,
+) -> FlockMetrics {
+    if boids.is_empty() {
+        return FlockMetrics::default();
+    }
+
+    let count = boids.len() as f32;
+    let wrap = config.wrap_bounds();
+
+    let heading_sum = boids
+        .iter()
+        .map(|boid| boid.velocity.normalize_or_zero())
+        .sum::<Vec2>();
+
+    let centroid = boids.iter().map(|boid| boid.position).sum::<Vec2>() / count;
+    let momentum_sum = boids
+        .iter()
+        .map(|boid| {
+            (boid.position - centroid)
+                .normalize_or_zero()
+                .perp_dot(boid.velocity.normalize_or_zero())
+        })
+        .sum::<f32>();
+
+    let speeds = boids
+        .iter()
+        .map(|boid| boid.velocity.length())
+        .collect::<Vec<_>>();
+    let speed_mean = speeds.iter().sum::<f32>() / count;
+    let speed_variance = speeds
+        .iter()
+        .map(|speed| (speed - speed_mean).powi(2))
+        .sum::<f32>()
+        / count;
+
+    let mut nearest_sum = 0.0;
+    let mut nearest_count = 0;
+
+    let slots = boids
+        .iter()
+        .enumerate()
+        .map(|(i, boid)| (boid.entity, i))
+        .collect::<HashMap<_, _>>();
+    let mut parents = (0..boids.len()).collect::<Vec<_>>();
+    for (i, boid) in boids.iter().enumerate() {
+        let visible_range = config
+            .species
+            .get(boid.species)
+            .map_or(0.0, |species| species.visible_range);
+
+        // the nearest neighbour comes out of the same query, so one further away than
+        // `visible_range` isn't counted
+        let mut nearest = f32::INFINITY;
+        index.query_radius_with_wrap(boid.position, visible_range, wrap, |position, other| {
+            if let Some(&j) = slots.get(&other.entity) {
+                let (a, b) = (find(&mut parents, i), find(&mut parents, j));
+                parents[a] = b;
+            }
+            if !other.predator && other.entity != boid.entity {
+                let distance = boundary::offset(wrap, boid.position, position).length();
+                nearest = nearest.min(distance);
+            }
+        });
+        if nearest.is_finite() {
+            nearest_sum += nearest;
+            nearest_count += 1;
+        }
+    }
+    let clusters = (0..boids.len())
+        .filter(|i| find(&mut parents, *i) == *i)
+        .count();
+
+    FlockMetrics {
+        polarization: heading_sum.length() / count,
+        milling: momentum_sum.abs() / count,
+        nearest_neighbor: if nearest_count > 0 {
+            nearest_sum / nearest_count as f32
+        } else {
+            0.0
+        },
+        speed_mean,
+        speed_std_dev: speed_variance.sqrt(),
+        speed_min: speeds.iter().copied().fold(f32::INFINITY, f32::min),
+        speed_max: speeds.iter().copied().fold(0.0, f32::max),
+        clusters,
+    }
+}
+
+/// Samples the metrics every `metrics_every` ticks while `metrics` is on.
+pub fn flock_metrics<I: SpatialIndex<EntityWrapper> + Resource>(
+    mut diagnostics: Diagnostics,
+    index: Res<I>,
+    boids: Query<(Entity, &Boid, &Species)>,
+    config: Query<&BoidConfiguration>,
+    mut ticks: Local<u32>,
+) {
+    let config = config.single();
+    if !config.metrics {
+        return;
+    }
+
+    let tick = *ticks;
+    *ticks = ticks.wrapping_add(1);
+    if !tick.is_multiple_of(config.metrics_every.max(1)) {
+        return;
+    }
+
+    let boids = boids
+        .iter()
+        .map(|(entity, boid, species)| MetricBoid {
+            entity,
+            position: boid.position,
+            velocity: boid.velocity,
+            species: species.0,
+        })
+        .collect::<Vec<_>>();
+
+    let metrics = compute(&boids, index.as_ref(), config);
+
+    diagnostics.add_measurement(&POLARIZATION, || metrics.polarization as f64);
+    diagnostics.add_measurement(&MILLING, || metrics.milling as f64);
+    diagnostics.add_measurement(&NEAREST_NEIGHBOR, || metrics.nearest_neighbor as f64);
+    diagnostics.add_measurement(&SPEED_MEAN, || metrics.speed_mean as f64);
+    diagnostics.add_measurement(&SPEED_STD_DEV, || metrics.speed_std_dev as f64);
+    diagnostics.add_measurement(&SPEED_MIN, || metrics.speed_min as f64);
+    diagnostics.add_measurement(&SPEED_MAX, || metrics.speed_max as f64);
+    diagnostics.add_measurement(&CLUSTERS, || metrics.clusters as f64);
+}
+
+#[cfg(test)]
+mod test {
+    use std::f32::consts::TAU;
+
+    use bevy::diagnostic::DiagnosticsStore;
+    use bevy::prelude::*;
+
+    use crate::config::{BoidConfiguration, SpeciesConfig};
+    use crate::flocking::EntityWrapper;
+    use crate::metrics::{compute, FlockMetrics, MetricBoid, POLARIZATION};
+    use crate::spatial::SpatialIndex;
+    use crate::uniform_grid::UniformGrid;
+    use crate::{test_app_with, BoidsPlugin};
+
+    fn metrics(boids: &[(Vec2, Vec2)]) -> FlockMetrics {
+        let config = BoidConfiguration {
+            species: vec![SpeciesConfig {
+                visible_range: 30.0,
+                ..default()
+            }],
+            ..default()
+        };
+
+        let boids = boids
+            .iter()
+            .enumerate()
+            .map(|(i, (position, velocity))| MetricBoid {
+                entity: Entity::from_raw(i as u32),
+                position: *position,
+                velocity: *velocity,
+                species: 0,
+            })
+            .collect::<Vec<_>>();
+
+        let mut grid = UniformGrid::new(Rect::new(-500.0, -500.0, 500.0, 500.0), 30.0);
+        grid.build(boids.iter().map(|boid| {
+            (
+                boid.position,
+                EntityWrapper {
+                    entity: boid.entity,
+                    velocity: boid.velocity,
+                    species: 0,
+                    predator: false,
+                },
+            )
+        }));
+
+        compute(&boids, &grid, &config)
+    }
+
+    #[test]
+    fn aligned_line() {
+        let line = (0..5)
+            .map(|i| (Vec2::new(i as f32 * 10.0, 0.0), Vec2::new(0.0, 50.0)))
+            .collect::<Vec<_>>();
+        let metrics = metrics(&line);
+
+        assert!((metrics.polarization - 1.0).abs() < 1e-5);
+        assert!((metrics.nearest_neighbor - 10.0).abs() < 1e-4);
+        assert_eq!(metrics.speed_mean, 50.0);
+        assert_eq!(metrics.speed_std_dev, 0.0);
+        assert_eq!(metrics.clusters, 1);
+    }
+
+    #[test]
+    fn milling_ring() {
+        let ring = (0..12)
+            .map(|i| {
+                let around = Vec2::from_angle(i as f32 * TAU / 12.0);
+                (around * 100.0, around.perp() * 40.0)
+            })
+            .collect::<Vec<_>>();
+        let metrics = metrics(&ring);
+
+        assert!(metrics.polarization < 1e-4);
+        assert!((metrics.milling - 1.0).abs() < 1e-4);
+        // neighbours on the ring are ~52 apart, out of visible range
+        assert_eq!(metrics.clusters, 12);
+    }
+
+    #[test]
+    fn separate_groups() {
+        let groups = [Vec2::new(-200.0, 0.0), Vec2::new(200.0, 0.0)]
+            .into_iter()
+            .flat_map(|centre| {
+                (0..4).map(move |i| {
+                    (
+                        centre + Vec2::new(i as f32 * 5.0, 0.0),
+                        Vec2::X * (i + 1) as f32,
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+        let metrics = metrics(&groups);
+
+        assert_eq!(metrics.clusters, 2);
+        assert_eq!(metrics.speed_min, 1.0);
+        assert_eq!(metrics.speed_max, 4.0);
+    }
+
+    fn samples(plugin: BoidsPlugin) -> usize {
+        // about 40 ticks at the default 64 ticks per second
+        let mut app = test_app_with(BoidsPlugin {
+            spawn_count: 20,
+            ..plugin
+        });
+        for _ in 0..41 {
+            app.update();
+        }
+
+        app.world()
+            .resource::<DiagnosticsStore>()
+            .get(&POLARIZATION)
+            .unwrap()
+            .history_len()
+    }
+
+    #[test]
+    fn samples_only_when_asked() {
+        assert_eq!(samples(BoidsPlugin::headless()), 0);
+
+        let every = BoidConfiguration {
+            metrics_every: 4,
+            ..default()
+        };
+        assert_eq!(
+            samples(BoidsPlugin {
+                metrics: true,
+                config: every,
+                ..BoidsPlugin::headless()
+            }),
+            10
+        );
+    }
+}
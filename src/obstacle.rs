@@ -0,0 +1,409 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_egui::egui;
+use bevy_egui::egui::lerp;
+use rand::Rng;
+
+use crate::boid::Boid;
+use crate::config::BoidConfiguration;
+
+/// Something boids steer around. The shape is relative to `position`.
+#[derive(Component, Clone, Debug)]
+pub struct Obstacle {
+    pub position: Vec2,
+    pub shape: ObstacleShape,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ObstacleShape {
+    Circle {
+        radius: f32,
+    },
+    Rectangle {
+        half_size: Vec2,
+    },
+    /// A convex polygon, vertices in counter-clockwise order.
+    Polygon {
+        vertices: Vec<Vec2>,
+    },
+}
+
+/// Where a ray first touches an obstacle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    pub distance: f32,
+    /// Outward surface normal at the hit.
+    pub normal: Vec2,
+}
+
+impl Obstacle {
+    pub fn new(position: Vec2, shape: ObstacleShape) -> Self {
+        Obstacle { position, shape }
+    }
+
+    /// Casts a ray from `origin` along the unit vector `direction`. A ray starting inside the
+    /// obstacle hits at distance 0, with the normal pointing out of the nearest side.
+    pub fn raycast(&self, origin: Vec2, direction: Vec2, max_distance: f32) -> Option<RayHit> {
+        let origin = origin - self.position;
+
+        match &self.shape {
+            ObstacleShape::Circle { radius } => {
+                raycast_circle(*radius, origin, direction, max_distance)
+            }
+            ObstacleShape::Rectangle { half_size } => {
+                let vertices = [
+                    Vec2::new(-half_size.x, -half_size.y),
+                    Vec2::new(half_size.x, -half_size.y),
+                    Vec2::new(half_size.x, half_size.y),
+                    Vec2::new(-half_size.x, half_size.y),
+                ];
+                raycast_polygon(&vertices, origin, direction, max_distance)
+            }
+            ObstacleShape::Polygon { vertices } => {
+                raycast_polygon(vertices, origin, direction, max_distance)
+            }
+        }
+    }
+}
+
+fn raycast_circle(radius: f32, origin: Vec2, direction: Vec2, max_distance: f32) -> Option<RayHit> {
+    if origin.length_squared() <= radius * radius {
+        return Some(RayHit {
+            distance: 0.0,
+            normal: origin.normalize_or(Vec2::Y),
+        });
+    }
+
+    // solve |origin + direction * t| = radius for the nearest t
+    let b = origin.dot(direction);
+    let c = origin.length_squared() - radius * radius;
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let distance = -b - discriminant.sqrt();
+    if distance < 0.0 || distance > max_distance {
+        return None;
+    }
+
+    Some(RayHit {
+        distance,
+        normal: (origin + direction * distance).normalize(),
+    })
+}
+
+// Cyrus-Beck clipping of the ray against every edge of a convex polygon
+fn raycast_polygon(
+    vertices: &[Vec2],
+    origin: Vec2,
+    direction: Vec2,
+    max_distance: f32,
+) -> Option<RayHit> {
+    if vertices.len() < 3 {
+        return None;
+    }
+
+    let mut enter = 0.0f32;
+    let mut exit = max_distance;
+    let mut enter_normal = None;
+
+    // the side the origin is least inside of, for rays that start inside
+    let mut nearest_side = (f32::NEG_INFINITY, Vec2::ZERO);
+
+    for (i, a) in vertices.iter().enumerate() {
+        let b = vertices[(i + 1) % vertices.len()];
+        let edge = b - *a;
+        let normal = Vec2::new(edge.y, -edge.x).normalize_or_zero();
+
+        // positive when the origin is outside this edge
+        let outside = (origin - *a).dot(normal);
+        let approach = direction.dot(normal);
+
+        if outside > nearest_side.0 {
+            nearest_side = (outside, normal);
+        }
+
+        if approach == 0.0 {
+            if outside > 0.0 {
+                return None;
+            }
+            continue;
+        }
+
+        let t = -outside / approach;
+        if approach < 0.0 {
+            if t > enter {
+                enter = t;
+                enter_normal = Some(normal);
+            }
+        } else {
+            exit = exit.min(t);
+        }
+
+        if enter > exit {
+            return None;
+        }
+    }
+
+    match enter_normal {
+        Some(normal) => Some(RayHit {
+            distance: enter,
+            normal,
+        }),
+        None if nearest_side.0 <= 0.0 => Some(RayHit {
+            distance: 0.0,
+            normal: nearest_side.1,
+        }),
+        None => None,
+    }
+}
+
+// Angles of the side feelers either side of the heading
+const FEELER_ANGLES: [f32; 3] = [0.0, 0.5, -0.5];
+
+/// Steers boids away from obstacles ahead of them. Feelers reach `obstacle_lookahead` along
+/// the heading and to either side; the closer a feeler hits, the harder the push along the
+/// surface normal.
+pub fn boid_avoid_obstacles(
+    mut boids: Query<&mut Boid>,
+    obstacles: Query<&Obstacle>,
+    config: Query<&BoidConfiguration>,
+) {
+    let config = config.single();
+
+    if obstacles.is_empty() || config.obstacle_avoid_factor == 0.0 {
+        return;
+    }
+
+    let lookahead = config.obstacle_lookahead.max(f32::EPSILON);
+
+    for mut boid in boids.iter_mut() {
+        let heading = boid.velocity.normalize_or_zero();
+        if heading == Vec2::ZERO {
+            continue;
+        }
+
+        let mut steer = Vec2::ZERO;
+        for angle in FEELER_ANGLES {
+            let direction = Vec2::from_angle(angle).rotate(heading);
+
+            let nearest = obstacles
+                .iter()
+                .filter_map(|obstacle| obstacle.raycast(boid.position, direction, lookahead))
+                .min_by(|a, b| a.distance.total_cmp(&b.distance));
+
+            if let Some(hit) = nearest {
+                steer += hit.normal * (1.0 - hit.distance / lookahead);
+            }
+        }
+
+        boid.velocity += steer * config.obstacle_avoid_factor;
+    }
+}
+
+pub fn render_obstacles_gizmo(
+    config: Query<&BoidConfiguration>,
+    obstacles: Query<&Obstacle>,
+    mut gizmos: Gizmos,
+) {
+    let config = config.single();
+
+    if !config.obstacle_gizmo.enabled {
+        return;
+    }
+
+    let color = Color::srgba(
+        config.obstacle_gizmo.color_rgba[0],
+        config.obstacle_gizmo.color_rgba[1],
+        config.obstacle_gizmo.color_rgba[2],
+        config.obstacle_gizmo.color_rgba[3],
+    );
+
+    for obstacle in obstacles.iter() {
+        match &obstacle.shape {
+            ObstacleShape::Circle { radius } => {
+                gizmos.circle_2d(obstacle.position, *radius, color);
+            }
+            ObstacleShape::Rectangle { half_size } => {
+                let iso = Isometry2d::from_translation(obstacle.position);
+                gizmos.rect_2d(iso, *half_size * 2.0, color);
+            }
+            ObstacleShape::Polygon { vertices } => {
+                let points = vertices
+                    .iter()
+                    .chain(vertices.first())
+                    .map(|vertex| obstacle.position + *vertex);
+                gizmos.linestrip_2d(points, color);
+            }
+        }
+    }
+}
+
+/// The obstacle section of `boids_ui`.
+#[derive(SystemParam)]
+pub struct ObstacleEditor<'w, 's> {
+    commands: Commands<'w, 's>,
+    obstacles: Query<'w, 's, (Entity, &'static Obstacle)>,
+    size: Local<'s, ObstacleSize>,
+}
+
+pub struct ObstacleSize(f32);
+
+impl Default for ObstacleSize {
+    fn default() -> Self {
+        ObstacleSize(40.0)
+    }
+}
+
+impl ObstacleEditor<'_, '_> {
+    pub fn ui(&mut self, ui: &mut egui::Ui, config: &BoidConfiguration, rng: &mut impl Rng) {
+        ui.horizontal(|ui| {
+            ui.label("size");
+            ui.add(egui::Slider::new(&mut self.size.0, 5.0..=200.0f32));
+        });
+
+        ui.horizontal(|ui| {
+            let size = self.size.0;
+            let shape = if ui.button("add circle").clicked() {
+                Some(ObstacleShape::Circle { radius: size })
+            } else if ui.button("add rectangle").clicked() {
+                Some(ObstacleShape::Rectangle {
+                    half_size: Vec2::new(size, size / 2.0),
+                })
+            } else if ui.button("add triangle").clicked() {
+                Some(ObstacleShape::Polygon {
+                    vertices: vec![
+                        Vec2::new(0.0, size),
+                        Vec2::new(-size, -size),
+                        Vec2::new(size, -size),
+                    ],
+                })
+            } else {
+                None
+            };
+
+            if let Some(shape) = shape {
+                let bounds = config.boid_bounds;
+                let position = Vec2::new(
+                    lerp(bounds.min.x..=bounds.max.x, rng.random::<f32>()),
+                    lerp(bounds.min.y..=bounds.max.y, rng.random::<f32>()),
+                );
+
+                self.commands
+                    .spawn((Name::new("obstacle"), Obstacle::new(position, shape)));
+            }
+
+            if ui.button("clear").clicked() {
+                for (entity, _) in self.obstacles.iter() {
+                    self.commands.entity(entity).despawn_recursive();
+                }
+            }
+        });
+
+        egui::Grid::new("obstacles").show(ui, |ui| {
+            for (entity, obstacle) in self.obstacles.iter() {
+                let kind = match obstacle.shape {
+                    ObstacleShape::Circle { .. } => "circle",
+                    ObstacleShape::Rectangle { .. } => "rectangle",
+                    ObstacleShape::Polygon { .. } => "polygon",
+                };
+                ui.label(kind);
+                ui.label(format!(
+                    "({:.0}, {:.0})",
+                    obstacle.position.x, obstacle.position.y
+                ));
+                if ui.button("remove").clicked() {
+                    self.commands.entity(entity).despawn_recursive();
+                }
+                ui.end_row();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::Vec2;
+
+    use crate::obstacle::{Obstacle, ObstacleShape};
+
+    #[test]
+    fn raycast_circle() {
+        let obstacle = Obstacle::new(Vec2::new(10.0, 0.0), ObstacleShape::Circle { radius: 2.0 });
+
+        let hit = obstacle.raycast(Vec2::ZERO, Vec2::X, 20.0).unwrap();
+        assert!((hit.distance - 8.0).abs() < 1e-4);
+        assert!(hit.normal.abs_diff_eq(Vec2::NEG_X, 1e-4));
+
+        assert!(
+            obstacle.raycast(Vec2::ZERO, Vec2::X, 5.0).is_none(),
+            "too short"
+        );
+        assert!(
+            obstacle.raycast(Vec2::ZERO, Vec2::Y, 20.0).is_none(),
+            "miss"
+        );
+        assert!(
+            obstacle.raycast(Vec2::ZERO, Vec2::NEG_X, 20.0).is_none(),
+            "behind"
+        );
+    }
+
+    #[test]
+    fn raycast_rectangle() {
+        let obstacle = Obstacle::new(
+            Vec2::new(0.0, 10.0),
+            ObstacleShape::Rectangle {
+                half_size: Vec2::new(5.0, 2.0),
+            },
+        );
+
+        let hit = obstacle.raycast(Vec2::ZERO, Vec2::Y, 20.0).unwrap();
+        assert!((hit.distance - 8.0).abs() < 1e-4);
+        assert!(hit.normal.abs_diff_eq(Vec2::NEG_Y, 1e-4));
+
+        assert!(obstacle
+            .raycast(Vec2::new(6.0, 0.0), Vec2::Y, 20.0)
+            .is_none());
+
+        let inside = obstacle
+            .raycast(Vec2::new(4.5, 10.0), Vec2::Y, 20.0)
+            .unwrap();
+        assert_eq!(inside.distance, 0.0);
+        assert!(inside.normal.abs_diff_eq(Vec2::X, 1e-4), "nearest side");
+    }
+
+    #[test]
+    fn raycast_polygon() {
+        let obstacle = Obstacle::new(
+            Vec2::ZERO,
+            ObstacleShape::Polygon {
+                vertices: vec![
+                    Vec2::new(0.0, 5.0),
+                    Vec2::new(-5.0, -5.0),
+                    Vec2::new(5.0, -5.0),
+                ],
+            },
+        );
+
+        let hit = obstacle
+            .raycast(Vec2::new(0.0, -20.0), Vec2::Y, 30.0)
+            .unwrap();
+        assert!((hit.distance - 15.0).abs() < 1e-4);
+        assert!(hit.normal.abs_diff_eq(Vec2::NEG_Y, 1e-4));
+
+        let slope = obstacle
+            .raycast(Vec2::new(-20.0, 0.0), Vec2::X, 40.0)
+            .unwrap();
+        assert!((slope.distance - 17.5).abs() < 1e-4);
+        assert!(slope.normal.x < 0.0 && slope.normal.y > 0.0);
+
+        assert!(
+            obstacle
+                .raycast(Vec2::new(-20.0, 6.0), Vec2::X, 40.0)
+                .is_none(),
+            "above the apex"
+        );
+    }
+}
@@ -0,0 +1,299 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_egui::egui;
+
+use crate::boid::Boid;
+use crate::config::BoidConfiguration;
+use crate::tool::{MouseTool, ToolMode};
+
+/// A point on the flock's path. Waypoints are visited in `index` order.
+#[derive(Component, Clone, Debug)]
+pub struct Waypoint {
+    pub position: Vec2,
+    pub index: usize,
+}
+
+/// Marks the waypoint the flock is currently heading for.
+#[derive(Component)]
+pub struct Goal;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PathMode {
+    /// Back to the first waypoint after the last.
+    #[default]
+    Loop,
+    /// Back and forth along the path.
+    PingPong,
+}
+
+/// Progress along the waypoints.
+#[derive(Resource, Debug)]
+pub struct PathFollow {
+    pub mode: PathMode,
+    /// Position of the goal in the waypoints sorted by `index`.
+    pub active: usize,
+    /// Direction of travel for `PathMode::PingPong`.
+    pub forward: bool,
+}
+
+impl Default for PathFollow {
+    fn default() -> Self {
+        PathFollow {
+            mode: PathMode::Loop,
+            active: 0,
+            forward: true,
+        }
+    }
+}
+
+impl PathFollow {
+    /// Moves on to the next waypoint of a path `len` long.
+    pub fn advance(&mut self, len: usize) {
+        if len < 2 {
+            self.active = 0;
+            return;
+        }
+
+        match self.mode {
+            PathMode::Loop => {
+                self.active = (self.active + 1) % len;
+            }
+            PathMode::PingPong => {
+                if self.forward && self.active + 1 >= len {
+                    self.forward = false;
+                } else if !self.forward && self.active == 0 {
+                    self.forward = true;
+                }
+
+                self.active = if self.forward {
+                    self.active + 1
+                } else {
+                    self.active - 1
+                };
+            }
+        }
+    }
+}
+
+fn sorted_waypoints<'a>(
+    waypoints: impl Iterator<Item = (Entity, &'a Waypoint)>,
+) -> Vec<(Entity, &'a Waypoint)> {
+    let mut waypoints = waypoints.collect::<Vec<_>>();
+    waypoints.sort_by_key(|(_, waypoint)| waypoint.index);
+    waypoints
+}
+
+/// Moves the `Goal` along the path once the centre of the flock is within `waypoint_radius` of
+/// it.
+pub fn path_advance(
+    mut commands: Commands,
+    mut path: ResMut<PathFollow>,
+    waypoints: Query<(Entity, &Waypoint, Has<Goal>)>,
+    boids: Query<&Boid>,
+    config: Query<&BoidConfiguration>,
+) {
+    let config = config.single();
+
+    let sorted = sorted_waypoints(
+        waypoints
+            .iter()
+            .map(|(entity, waypoint, _)| (entity, waypoint)),
+    );
+    if sorted.is_empty() {
+        return;
+    }
+
+    if path.active >= sorted.len() {
+        path.active = 0;
+    }
+
+    let count = boids.iter().count();
+    if count > 0 {
+        let centroid = boids.iter().map(|boid| boid.position).sum::<Vec2>() / count as f32;
+        if centroid.distance(sorted[path.active].1.position) <= config.waypoint_radius {
+            path.advance(sorted.len());
+        }
+    }
+
+    let goal = sorted[path.active].0;
+    for (entity, _, is_goal) in waypoints.iter() {
+        if entity == goal && !is_goal {
+            commands.entity(entity).insert(Goal);
+        } else if entity != goal && is_goal {
+            commands.entity(entity).remove::<Goal>();
+        }
+    }
+}
+
+/// Pulls every boid towards the goal with `goal_weight`.
+pub fn boid_seek_goal(
+    mut boids: Query<&mut Boid>,
+    goal: Query<&Waypoint, With<Goal>>,
+    config: Query<&BoidConfiguration>,
+) {
+    let config = config.single();
+
+    let Ok(goal) = goal.get_single() else {
+        return;
+    };
+
+    if config.goal_weight == 0.0 {
+        return;
+    }
+
+    for mut boid in boids.iter_mut() {
+        let towards = (goal.position - boid.position).normalize_or_zero();
+        boid.velocity += towards * config.goal_weight;
+    }
+}
+
+/// With the path tool, left click adds a waypoint at the end of the path and right click removes
+/// the nearest one.
+pub fn path_edit_waypoints(
+    mut commands: Commands,
+    tool: Res<MouseTool>,
+    waypoints: Query<(Entity, &Waypoint)>,
+    mouse: Res<ButtonInput<MouseButton>>,
+) {
+    if tool.mode != ToolMode::Path {
+        return;
+    }
+
+    let left = mouse.just_pressed(MouseButton::Left);
+    let right = mouse.just_pressed(MouseButton::Right);
+    if !left && !right {
+        return;
+    }
+
+    // `None` while the pointer is off the window or over the UI
+    let Some(mouse) = tool.cursor else {
+        return;
+    };
+
+    if left {
+        let index = waypoints
+            .iter()
+            .map(|(_, waypoint)| waypoint.index + 1)
+            .max()
+            .unwrap_or(0);
+
+        commands.spawn((
+            Name::new("waypoint"),
+            Waypoint {
+                position: mouse,
+                index,
+            },
+        ));
+    }
+
+    if right {
+        if let Some((entity, _)) = waypoints.iter().min_by(|(_, a), (_, b)| {
+            a.position
+                .distance_squared(mouse)
+                .total_cmp(&b.position.distance_squared(mouse))
+        }) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+pub fn render_path_gizmo(
+    config: Query<&BoidConfiguration>,
+    path: Res<PathFollow>,
+    waypoints: Query<(Entity, &Waypoint, Has<Goal>)>,
+    mut gizmos: Gizmos,
+) {
+    let config = config.single();
+
+    if !config.path_gizmo.enabled {
+        return;
+    }
+
+    let color = Color::srgba(
+        config.path_gizmo.color_rgba[0],
+        config.path_gizmo.color_rgba[1],
+        config.path_gizmo.color_rgba[2],
+        config.path_gizmo.color_rgba[3],
+    );
+
+    let sorted = sorted_waypoints(
+        waypoints
+            .iter()
+            .map(|(entity, waypoint, _)| (entity, waypoint)),
+    );
+
+    let points = sorted.iter().map(|(_, waypoint)| waypoint.position);
+    match path.mode {
+        PathMode::Loop if sorted.len() > 2 => {
+            gizmos.linestrip_2d(points.chain(sorted.first().map(|(_, w)| w.position)), color)
+        }
+        _ => gizmos.linestrip_2d(points, color),
+    }
+
+    for (_, waypoint, is_goal) in waypoints.iter() {
+        gizmos.circle_2d(waypoint.position, config.waypoint_radius, color);
+        if is_goal {
+            gizmos.circle_2d(waypoint.position, config.waypoint_radius * 0.25, color);
+        }
+    }
+}
+
+/// The path section of `boids_ui`.
+#[derive(SystemParam)]
+pub struct PathEditor<'w, 's> {
+    commands: Commands<'w, 's>,
+    path: ResMut<'w, PathFollow>,
+    waypoints: Query<'w, 's, Entity, With<Waypoint>>,
+}
+
+impl PathEditor<'_, '_> {
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.path.mode, PathMode::Loop, "Loop");
+            ui.radio_value(&mut self.path.mode, PathMode::PingPong, "PingPong");
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("place waypoints with the Path tool");
+
+            if ui.button("clear").clicked() {
+                for entity in self.waypoints.iter() {
+                    self.commands.entity(entity).despawn_recursive();
+                }
+                self.path.active = 0;
+                self.path.forward = true;
+            }
+        });
+
+        ui.label(format!(
+            "{} waypoints, heading for #{}",
+            self.waypoints.iter().count(),
+            self.path.active + 1
+        ));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::path::{PathFollow, PathMode};
+
+    fn visits(mode: PathMode, len: usize, steps: usize) -> Vec<usize> {
+        let mut path = PathFollow {
+            mode,
+            ..Default::default()
+        };
+        (0..steps)
+            .map(|_| {
+                path.advance(len);
+                path.active
+            })
+            .collect()
+    }
+
+    #[test]
+    fn advance() {
+        assert_eq!(visits(PathMode::Loop, 3, 5), vec![1, 2, 0, 1, 2]);
+        assert_eq!(visits(PathMode::PingPong, 3, 6), vec![1, 2, 1, 0, 1, 2]);
+        assert_eq!(visits(PathMode::PingPong, 1, 2), vec![0, 0]);
+    }
+}
@@ -0,0 +1,403 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use bevy::diagnostic::{
+    Diagnostic, DiagnosticPath, Diagnostics, DiagnosticsStore, FrameTimeDiagnosticsPlugin,
+    RegisterDiagnostic,
+};
+use bevy::prelude::*;
+use bevy::utils::{Duration, Instant};
+use bevy_egui::egui;
+
+use crate::flocking::SpatialState;
+
+/// Milliseconds spent in the fixed timestep loop this frame, however many ticks it ran.
+pub const SIMULATION_TIME: DiagnosticPath = DiagnosticPath::const_new("boids/simulation_time");
+/// Milliseconds spent between `BoidSet::Ui` and the end of `BoidSet::Render` this frame.
+pub const UPDATE_TIME: DiagnosticPath = DiagnosticPath::const_new("boids/update_time");
+/// The rest of the last frame time: rendering, the window and everything outside the boids.
+pub const OTHER_TIME: DiagnosticPath = DiagnosticPath::const_new("boids/other_time");
+/// Points the spatial index looked at for the flocking queries in one tick, before any distance
+/// test, summed over every boid. How much less than every pair this is shows what the index saves.
+pub const NEIGHBOR_CHECKS: DiagnosticPath = DiagnosticPath::const_new("boids/neighbor_checks");
+/// Candidates that turned out to be visible neighbours, summed over every boid.
+pub const NEIGHBORS_ACCEPTED: DiagnosticPath =
+    DiagnosticPath::const_new("boids/neighbors_accepted");
+/// Milliseconds `populate_index` took to rebuild the spatial index.
+pub const INDEX_BUILD_TIME: DiagnosticPath = DiagnosticPath::const_new("boids/index_build_time");
+/// Milliseconds `boid_flocking` took. Its neighbour queries run the rules as they go, so this
+/// is queries and rules together.
+pub const INDEX_QUERY_TIME: DiagnosticPath = DiagnosticPath::const_new("boids/index_query_time");
+/// Heap allocations made by the build and the queries in one tick. Only measured when the app
+/// uses `CountingAllocator`.
+pub const INDEX_ALLOCATIONS: DiagnosticPath = DiagnosticPath::const_new("boids/index_allocations");
+
+pub fn register(app: &mut App) {
+    for path in [
+        SIMULATION_TIME,
+        UPDATE_TIME,
+        OTHER_TIME,
+        INDEX_BUILD_TIME,
+        INDEX_QUERY_TIME,
+    ] {
+        app.register_diagnostic(Diagnostic::new(path).with_suffix("ms"));
+    }
+    for path in [NEIGHBOR_CHECKS, NEIGHBORS_ACCEPTED, INDEX_ALLOCATIONS] {
+        app.register_diagnostic(Diagnostic::new(path).with_smoothing_factor(0.0));
+    }
+}
+
+static COUNTING: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static THREAD_ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// The system allocator, counting allocations per thread for `INDEX_ALLOCATIONS`. Install it in
+/// the binary with `#[global_allocator]`.
+pub struct CountingAllocator;
+
+impl CountingAllocator {
+    fn count() {
+        COUNTING.store(true, Ordering::Relaxed);
+        // `try_with` since the thread local may already be gone while a thread shuts down
+        let _ = THREAD_ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        Self::count();
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        Self::count();
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        Self::count();
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// Times a stretch of a system and counts the allocations the current thread makes in it.
+pub struct Span {
+    start: Instant,
+    allocations: u64,
+}
+
+impl Span {
+    pub fn start() -> Self {
+        Span {
+            start: Instant::now(),
+            allocations: THREAD_ALLOCATIONS.with(Cell::get),
+        }
+    }
+
+    pub fn end(self) -> (Duration, u64) {
+        (
+            self.start.elapsed(),
+            THREAD_ALLOCATIONS.with(Cell::get) - self.allocations,
+        )
+    }
+}
+
+/// What the spatial index did this tick, filled in by `populate_index` and `boid_flocking`.
+#[derive(Resource, Default)]
+pub struct IndexProfile {
+    pub build: Duration,
+    pub query: Duration,
+    pub candidates: u64,
+    pub accepted: u64,
+    pub allocations: u64,
+}
+
+/// Per tick averages for one spatial index.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IndexStats {
+    pub ticks: u64,
+    pub build_ms: f64,
+    pub query_ms: f64,
+    pub candidates: f64,
+    pub accepted: f64,
+    pub allocations: Option<f64>,
+}
+
+impl IndexStats {
+    /// Weight of the newest tick in the averages, so they follow slider changes within a second
+    /// or two.
+    const SMOOTHING: f64 = 0.05;
+
+    fn add(&mut self, profile: &IndexProfile, allocations: Option<u64>) {
+        let alpha = if self.ticks == 0 {
+            1.0
+        } else {
+            Self::SMOOTHING
+        };
+        let smooth = |average: f64, value: f64| average + (value - average) * alpha;
+
+        self.ticks += 1;
+        self.build_ms = smooth(self.build_ms, profile.build.as_secs_f64() * 1000.0);
+        self.query_ms = smooth(self.query_ms, profile.query.as_secs_f64() * 1000.0);
+        self.candidates = smooth(self.candidates, profile.candidates as f64);
+        self.accepted = smooth(self.accepted, profile.accepted as f64);
+        self.allocations = allocations
+            .map(|allocations| smooth(self.allocations.unwrap_or(0.0), allocations as f64));
+    }
+}
+
+/// `IndexStats` for every spatial index that has run, so they can be compared after switching.
+#[derive(Resource, Default)]
+pub struct IndexComparison(pub BTreeMap<SpatialState, IndexStats>);
+
+pub fn profile_index_start(mut profile: ResMut<IndexProfile>) {
+    *profile = IndexProfile::default();
+}
+
+pub fn profile_index_end(
+    profile: Res<IndexProfile>,
+    state: Res<State<SpatialState>>,
+    mut comparison: ResMut<IndexComparison>,
+    mut diagnostics: Diagnostics,
+) {
+    let allocations = COUNTING
+        .load(Ordering::Relaxed)
+        .then_some(profile.allocations);
+
+    comparison
+        .0
+        .entry(state.get().clone())
+        .or_default()
+        .add(&profile, allocations);
+
+    diagnostics.add_measurement(&INDEX_BUILD_TIME, || profile.build.as_secs_f64() * 1000.0);
+    diagnostics.add_measurement(&INDEX_QUERY_TIME, || profile.query.as_secs_f64() * 1000.0);
+    diagnostics.add_measurement(&NEIGHBOR_CHECKS, || profile.candidates as f64);
+    diagnostics.add_measurement(&NEIGHBORS_ACCEPTED, || profile.accepted as f64);
+    if let Some(allocations) = allocations {
+        diagnostics.add_measurement(&INDEX_ALLOCATIONS, || allocations as f64);
+    }
+}
+
+/// The index comparison table in `boids_ui`, one column per index that has run.
+pub fn comparison_ui(ui: &mut egui::Ui, comparison: &IndexComparison, current: &SpatialState) {
+    if comparison.0.is_empty() {
+        ui.label("no ticks yet");
+        return;
+    }
+
+    egui::Grid::new("index_comparison")
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("per tick");
+            for state in comparison.0.keys() {
+                let name = format!("{:?}", state);
+                if state == current {
+                    ui.strong(name);
+                } else {
+                    ui.label(name);
+                }
+            }
+            ui.end_row();
+
+            let row = |ui: &mut egui::Ui, label: &str, value: &dyn Fn(&IndexStats) -> String| {
+                ui.label(label);
+                for stats in comparison.0.values() {
+                    ui.label(value(stats));
+                }
+                ui.end_row();
+            };
+
+            row(ui, "build ms", &|stats| format!("{:.3}", stats.build_ms));
+            row(ui, "query ms", &|stats| format!("{:.3}", stats.query_ms));
+            row(ui, "candidates", &|stats| {
+                format!("{:.0}", stats.candidates)
+            });
+            row(ui, "accepted", &|stats| format!("{:.0}", stats.accepted));
+            row(ui, "accepted %", &|stats| {
+                if stats.candidates > 0.0 {
+                    format!("{:.1}", stats.accepted / stats.candidates * 100.0)
+                } else {
+                    "-".to_string()
+                }
+            });
+            row(ui, "allocations", &|stats| match stats.allocations {
+                Some(allocations) => format!("{:.0}", allocations),
+                None => "-".to_string(),
+            });
+            row(ui, "ticks", &|stats| stats.ticks.to_string());
+        });
+}
+
+#[derive(Resource, Default)]
+pub struct FrameTimer {
+    simulation_start: Option<Instant>,
+    simulation: Duration,
+    update_start: Option<Instant>,
+}
+
+pub fn perf_simulation_start(mut timer: ResMut<FrameTimer>) {
+    timer.simulation_start = Some(Instant::now());
+}
+
+pub fn perf_simulation_end(mut timer: ResMut<FrameTimer>) {
+    if let Some(start) = timer.simulation_start.take() {
+        timer.simulation = start.elapsed();
+    }
+}
+
+pub fn perf_update_start(mut timer: ResMut<FrameTimer>) {
+    timer.update_start = Some(Instant::now());
+}
+
+pub fn perf_update_end(
+    mut timer: ResMut<FrameTimer>,
+    mut diagnostics: Diagnostics,
+    store: Res<DiagnosticsStore>,
+) {
+    let Some(start) = timer.update_start.take() else {
+        return;
+    };
+    let simulation = timer.simulation.as_secs_f64() * 1000.0;
+    let update = start.elapsed().as_secs_f64() * 1000.0;
+
+    diagnostics.add_measurement(&SIMULATION_TIME, || simulation);
+    diagnostics.add_measurement(&UPDATE_TIME, || update);
+
+    // the frame time is the previous frame's, so this is only a rough split
+    if let Some(frame) = store
+        .get(&FrameTimeDiagnosticsPlugin::FRAME_TIME)
+        .and_then(|frame| frame.value())
+    {
+        diagnostics.add_measurement(&OTHER_TIME, || (frame - simulation - update).max(0.0));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::prelude::*;
+    use bevy::utils::Duration;
+
+    use crate::perf::{IndexComparison, IndexProfile, IndexStats};
+    use crate::{test_app, test_app_with, BoidsPlugin, SpatialState};
+
+    fn profile(build_ms: u64, candidates: u64) -> IndexProfile {
+        IndexProfile {
+            build: Duration::from_millis(build_ms),
+            query: Duration::from_millis(build_ms * 2),
+            candidates,
+            accepted: candidates / 2,
+            allocations: 0,
+        }
+    }
+
+    #[test]
+    fn averages_each_tick() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        let mut stats = IndexStats::default();
+
+        // the first tick is taken as it is
+        stats.add(&profile(10, 100), None);
+        assert_eq!(stats.ticks, 1);
+        assert!(close(stats.build_ms, 10.0));
+        assert!(close(stats.query_ms, 20.0));
+        assert!(close(stats.candidates, 100.0));
+        assert!(close(stats.accepted, 50.0));
+        assert_eq!(stats.allocations, None);
+
+        // later ones move the averages by `SMOOTHING` of the difference
+        stats.add(&profile(30, 300), Some(40));
+        assert_eq!(stats.ticks, 2);
+        assert!(close(stats.build_ms, 10.0 + 20.0 * IndexStats::SMOOTHING));
+        assert!(close(stats.query_ms, 20.0 + 40.0 * IndexStats::SMOOTHING));
+        assert!(close(
+            stats.candidates,
+            100.0 + 200.0 * IndexStats::SMOOTHING
+        ));
+        assert!(close(stats.accepted, 50.0 + 100.0 * IndexStats::SMOOTHING));
+        assert!(close(
+            stats.allocations.unwrap(),
+            40.0 * IndexStats::SMOOTHING
+        ));
+
+        stats.add(&profile(30, 300), None);
+        assert_eq!(stats.allocations, None, "only shown while counted");
+    }
+
+    #[test]
+    fn compares_each_spatial_index() {
+        let mut app = test_app(200, Some(5));
+
+        let states = [
+            SpatialState::QuadTree,
+            SpatialState::SpatialHash,
+            SpatialState::UniformGrid,
+        ];
+        for state in states.iter() {
+            app.world_mut()
+                .resource_mut::<NextState<SpatialState>>()
+                .set(state.clone());
+            for _ in 0..10 {
+                app.update();
+            }
+        }
+
+        let comparison = &app.world().resource::<IndexComparison>().0;
+        for state in states.iter() {
+            let stats = comparison.get(state).expect("every index ran");
+            assert!(stats.ticks > 0, "{:?}", state);
+            assert!(stats.accepted > 0.0, "{:?}", state);
+            assert!(stats.candidates >= stats.accepted, "{:?}", state);
+            // the counting allocator is only installed in tests/allocations.rs
+            assert!(stats.allocations.is_none(), "{:?}", state);
+        }
+    }
+
+    #[test]
+    fn counts_what_each_index_examines() {
+        // the first tick of the same flock under each index
+        let first_tick = |state: SpatialState| {
+            let mut app = test_app_with(BoidsPlugin {
+                spatial_state: state.clone(),
+                spawn_count: 200,
+                seed: Some(5),
+                ..BoidsPlugin::headless()
+            });
+            let stats = (0..10)
+                .find_map(|_| {
+                    app.update();
+                    app.world()
+                        .resource::<IndexComparison>()
+                        .0
+                        .get(&state)
+                        .copied()
+                })
+                .expect("a fixed tick within ten updates");
+            assert_eq!(stats.ticks, 1);
+            stats
+        };
+
+        let quadtree = first_tick(SpatialState::QuadTree);
+        let hash = first_tick(SpatialState::SpatialHash);
+        let grid = first_tick(SpatialState::UniformGrid);
+
+        // the same neighbours are found, by looking through different cells and nodes
+        assert_eq!(quadtree.accepted, hash.accepted);
+        assert_eq!(quadtree.accepted, grid.accepted);
+        for stats in [quadtree, hash, grid] {
+            assert!(stats.candidates > stats.accepted);
+        }
+        assert_ne!(quadtree.candidates, hash.candidates);
+        // the hash and the grid share `spatial_hash_size`, but distant cells can collide in a hash
+        // bucket, so the hash looks through at least as many points
+        assert!(hash.candidates >= grid.candidates);
+    }
+}
@@ -0,0 +1,451 @@
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use bevy::diagnostic::{DiagnosticPath, DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{metrics, perf};
+
+/// Window lengths a graph can show, in seconds. The last is how much history is kept.
+pub const WINDOWS: [f64; 5] = [5.0, 10.0, 30.0, 60.0, 120.0];
+
+const COLORS: [egui::Color32; 4] = [
+    egui::Color32::from_rgb(100, 180, 255),
+    egui::Color32::from_rgb(255, 160, 80),
+    egui::Color32::from_rgb(120, 220, 120),
+    egui::Color32::from_rgb(230, 110, 200),
+];
+
+pub struct Series {
+    pub path: DiagnosticPath,
+    pub label: &'static str,
+}
+
+pub struct Graph {
+    pub title: &'static str,
+    pub series: &'static [Series],
+}
+
+pub const GRAPHS: [Graph; 8] = [
+    Graph {
+        title: "fps",
+        series: &[Series {
+            path: FrameTimeDiagnosticsPlugin::FPS,
+            label: "fps",
+        }],
+    },
+    Graph {
+        title: "frame time",
+        series: &[
+            Series {
+                path: FrameTimeDiagnosticsPlugin::FRAME_TIME,
+                label: "frame ms",
+            },
+            Series {
+                path: perf::SIMULATION_TIME,
+                label: "simulation ms",
+            },
+            Series {
+                path: perf::UPDATE_TIME,
+                label: "update ms",
+            },
+            Series {
+                path: perf::OTHER_TIME,
+                label: "other ms",
+            },
+        ],
+    },
+    Graph {
+        title: "neighbour checks",
+        series: &[
+            Series {
+                path: perf::NEIGHBOR_CHECKS,
+                label: "examined",
+            },
+            Series {
+                path: perf::NEIGHBORS_ACCEPTED,
+                label: "accepted",
+            },
+        ],
+    },
+    Graph {
+        title: "spatial index",
+        series: &[
+            Series {
+                path: perf::INDEX_BUILD_TIME,
+                label: "build ms",
+            },
+            Series {
+                path: perf::INDEX_QUERY_TIME,
+                label: "query ms",
+            },
+        ],
+    },
+    Graph {
+        title: "order",
+        series: &[
+            Series {
+                path: metrics::POLARIZATION,
+                label: "polarization",
+            },
+            Series {
+                path: metrics::MILLING,
+                label: "milling",
+            },
+        ],
+    },
+    Graph {
+        title: "speed",
+        series: &[
+            Series {
+                path: metrics::SPEED_MEAN,
+                label: "mean",
+            },
+            Series {
+                path: metrics::SPEED_MIN,
+                label: "min",
+            },
+            Series {
+                path: metrics::SPEED_MAX,
+                label: "max",
+            },
+            Series {
+                path: metrics::SPEED_STD_DEV,
+                label: "std dev",
+            },
+        ],
+    },
+    Graph {
+        title: "nearest neighbour",
+        series: &[Series {
+            path: metrics::NEAREST_NEIGHBOR,
+            label: "mean distance",
+        }],
+    },
+    Graph {
+        title: "clusters",
+        series: &[Series {
+            path: metrics::CLUSTERS,
+            label: "clusters",
+        }],
+    },
+];
+
+/// The latest value of every graphed diagnostic, sampled once a frame. Unlike the diagnostics'
+/// own history this covers the longest window regardless of frame rate.
+#[derive(Resource, Default)]
+pub struct PlotHistory {
+    /// Real time in seconds of each sample.
+    times: VecDeque<f64>,
+    /// One value per sample for each series, NaN where the diagnostic had no value.
+    values: HashMap<DiagnosticPath, VecDeque<f64>>,
+}
+
+impl PlotHistory {
+    pub fn push(&mut self, time: f64, mut sample: impl FnMut(&DiagnosticPath) -> Option<f64>) {
+        self.times.push_back(time);
+        for series in GRAPHS.iter().flat_map(|graph| graph.series) {
+            self.values
+                .entry(series.path.clone())
+                .or_default()
+                .push_back(sample(&series.path).unwrap_or(f64::NAN));
+        }
+
+        let oldest = time - WINDOWS[WINDOWS.len() - 1];
+        while self.times.front().is_some_and(|time| *time < oldest) {
+            self.times.pop_front();
+            for values in self.values.values_mut() {
+                values.pop_front();
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.times.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.times.is_empty()
+    }
+
+    /// The samples within `window` seconds of the latest.
+    pub fn visible(&self, window: f64) -> Range<usize> {
+        let Some(latest) = self.times.back() else {
+            return 0..0;
+        };
+        self.times.partition_point(|time| *time < latest - window)..self.times.len()
+    }
+
+    /// The visible samples of `graph` with a `time` column first. Missing values are left empty.
+    pub fn csv(&self, graph: &Graph, window: f64) -> String {
+        let mut csv = String::from("time");
+        for series in graph.series {
+            csv.push(',');
+            csv.push_str(series.label);
+        }
+        csv.push('\n');
+
+        for i in self.visible(window) {
+            let _ = write!(csv, "{:.3}", self.times[i]);
+            for series in graph.series {
+                csv.push(',');
+                if let Some(value) = self
+                    .value(&series.path, i)
+                    .filter(|value| value.is_finite())
+                {
+                    let _ = write!(csv, "{}", value);
+                }
+            }
+            csv.push('\n');
+        }
+
+        csv
+    }
+
+    fn value(&self, path: &DiagnosticPath, i: usize) -> Option<f64> {
+        self.values
+            .get(path)
+            .and_then(|values| values.get(i))
+            .copied()
+    }
+}
+
+pub fn plots_sample(
+    time: Res<Time<Real>>,
+    diagnostics: Res<DiagnosticsStore>,
+    mut history: ResMut<PlotHistory>,
+) {
+    history.push(time.elapsed_secs_f64(), |path| {
+        diagnostics
+            .get(path)
+            .and_then(|diagnostic| diagnostic.value())
+    });
+}
+
+pub struct PlotsState {
+    windows: [f64; GRAPHS.len()],
+    status: Option<String>,
+}
+
+impl Default for PlotsState {
+    fn default() -> Self {
+        PlotsState {
+            windows: [30.0; GRAPHS.len()],
+            status: None,
+        }
+    }
+}
+
+pub fn plots_ui(
+    mut contexts: EguiContexts,
+    history: Res<PlotHistory>,
+    mut state: Local<PlotsState>,
+) {
+    let state = &mut *state;
+
+    egui::Window::new("plots")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            for (graph, window) in GRAPHS.iter().zip(state.windows.iter_mut()) {
+                egui::CollapsingHeader::new(graph.title).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_salt(("plot window", graph.title))
+                            .selected_text(format!("{}s", window))
+                            .show_ui(ui, |ui| {
+                                for length in WINDOWS {
+                                    ui.selectable_value(window, length, format!("{}s", length));
+                                }
+                            });
+
+                        if ui.button("export csv").clicked() {
+                            let path = PathBuf::from("exports")
+                                .join(format!("plot_{}.csv", graph.title.replace(' ', "_")));
+                            let csv = history.csv(graph, *window);
+                            let written = std::fs::create_dir_all("exports")
+                                .and_then(|()| std::fs::write(&path, csv));
+                            state.status = Some(match written {
+                                Ok(()) => format!("wrote {}", path.display()),
+                                Err(error) => error.to_string(),
+                            });
+                        }
+                    });
+
+                    plot(ui, &history, graph, *window);
+                });
+            }
+
+            if let Some(status) = &state.status {
+                ui.label(status);
+            }
+        });
+}
+
+/// Draws the visible samples of `graph` as lines scaled to fit, with a legend of latest values.
+fn plot(ui: &mut egui::Ui, history: &PlotHistory, graph: &Graph, window: f64) {
+    let size = egui::vec2(ui.available_width().max(240.0), 100.0);
+    let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+    let visible = history.visible(window);
+    let Some(&latest) = history.times.back() else {
+        return;
+    };
+    let start = latest - window;
+
+    let (mut min, mut max) = (f64::INFINITY, f64::NEG_INFINITY);
+    for series in graph.series {
+        for i in visible.clone() {
+            if let Some(value) = history.value(&series.path, i).filter(|v| v.is_finite()) {
+                min = min.min(value);
+                max = max.max(value);
+            }
+        }
+    }
+    if !min.is_finite() {
+        return;
+    }
+    if max == min {
+        min -= 0.5;
+        max += 0.5;
+    }
+
+    let to_screen = |time: f64, value: f64| {
+        egui::pos2(
+            rect.left() + ((time - start) / window) as f32 * rect.width(),
+            rect.bottom() - ((value - min) / (max - min)) as f32 * rect.height(),
+        )
+    };
+
+    // at most about one point per pixel
+    let step = (visible.len() / rect.width().max(1.0) as usize).max(1);
+    for (series, color) in graph.series.iter().zip(COLORS.iter().cycle()) {
+        let stroke = egui::Stroke::new(1.5, *color);
+        let mut line = Vec::new();
+        for i in visible.clone().step_by(step) {
+            match history.value(&series.path, i).filter(|v| v.is_finite()) {
+                Some(value) => line.push(to_screen(history.times[i], value)),
+                // a gap in the series breaks the line
+                None if line.len() > 1 => {
+                    painter.add(egui::Shape::line(std::mem::take(&mut line), stroke));
+                }
+                None => line.clear(),
+            }
+        }
+        if line.len() > 1 {
+            painter.add(egui::Shape::line(line, stroke));
+        }
+    }
+
+    let font = egui::FontId::monospace(10.0);
+    let text = ui.visuals().weak_text_color();
+    painter.text(
+        rect.left_top() + egui::vec2(2.0, 2.0),
+        egui::Align2::LEFT_TOP,
+        format!("{:.2}", max),
+        font.clone(),
+        text,
+    );
+    painter.text(
+        rect.left_bottom() + egui::vec2(2.0, -2.0),
+        egui::Align2::LEFT_BOTTOM,
+        format!("{:.2}", min),
+        font,
+        text,
+    );
+
+    ui.horizontal_wrapped(|ui| {
+        for (series, color) in graph.series.iter().zip(COLORS.iter().cycle()) {
+            let latest = history
+                .value(&series.path, history.len() - 1)
+                .filter(|value| value.is_finite());
+            let label = match latest {
+                Some(value) => format!("{} {:.2}", series.label, value),
+                None => series.label.to_string(),
+            };
+            ui.colored_label(*color, label);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+
+    use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+    use bevy::prelude::*;
+
+    use crate::plots::{PlotHistory, GRAPHS, WINDOWS};
+    use crate::{metrics, test_app_with, BoidConfiguration, BoidsPlugin, SpatialState};
+
+    #[test]
+    fn keeps_the_longest_window() {
+        let mut history = PlotHistory::default();
+        for i in 0..=300 {
+            let time = i as f64;
+            history.push(time, |path| {
+                if *path == FrameTimeDiagnosticsPlugin::FPS {
+                    Some(60.0 + time)
+                } else if *path == metrics::MILLING && i % 2 == 0 {
+                    Some(0.5)
+                } else {
+                    None
+                }
+            });
+        }
+
+        assert_eq!(history.len(), WINDOWS[WINDOWS.len() - 1] as usize + 1);
+        assert_eq!(history.visible(5.0), history.len() - 6..history.len());
+
+        let fps = GRAPHS.iter().find(|graph| graph.title == "fps").unwrap();
+        assert_eq!(
+            history.csv(fps, 2.0),
+            "time,fps\n298.000,358\n299.000,359\n300.000,360\n"
+        );
+
+        let order = GRAPHS.iter().find(|graph| graph.title == "order").unwrap();
+        assert_eq!(
+            history.csv(order, 1.0),
+            "time,polarization,milling\n299.000,,\n300.000,,0.5\n"
+        );
+    }
+
+    #[test]
+    fn neighbour_checks_count_every_point_examined() {
+        const COUNT: u32 = 20;
+
+        // one cell holding the whole flock, which is spread wider than `visible_range`
+        let mut app = test_app_with(BoidsPlugin {
+            spatial_state: SpatialState::UniformGrid,
+            spawn_count: COUNT,
+            config: BoidConfiguration {
+                spatial_hash_size: 1000,
+                ..default()
+            },
+            ..BoidsPlugin::headless()
+        });
+        for _ in 0..5 {
+            app.update();
+        }
+
+        let graph = GRAPHS
+            .iter()
+            .find(|graph| graph.title == "neighbour checks")
+            .unwrap();
+        let store = app.world().resource::<DiagnosticsStore>();
+        let [examined, accepted] = [0, 1].map(|i| {
+            store
+                .get(&graph.series[i].path)
+                .and_then(|diagnostic| diagnostic.value())
+                .unwrap()
+        });
+
+        // each boid's query looks through the whole cell, itself and boids out of range included
+        assert_eq!(examined, (COUNT * COUNT) as f64);
+        assert!(accepted > 0.0);
+        assert!(accepted < (COUNT * (COUNT - 1)) as f64);
+    }
+}
@@ -0,0 +1,357 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use bevy_egui::egui;
+use bevy_egui::egui::lerp;
+use rand::Rng;
+
+use crate::boid::{Boid, PreviousPosition, Species};
+use crate::boundary;
+use crate::config::BoidConfiguration;
+use crate::flocking::EntityWrapper;
+use crate::spatial::SpatialIndex;
+
+/// How a predator picks what to chase.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChaseStrategy {
+    /// The closest boid.
+    #[default]
+    Nearest,
+    /// The centre of the whole flock, which splits it down the middle.
+    Centroid,
+    /// The boid within `predator_hunt_range` with the fewest neighbours of its own.
+    Straggler,
+}
+
+/// Something boids flee from. Predators live in the spatial index alongside the boids, flagged
+/// with `EntityWrapper::predator`.
+#[derive(Component, Clone, Debug)]
+pub struct Predator {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub max_speed: f32,
+    pub min_speed: f32,
+    /// Fraction of the gap between the current and the desired velocity closed each tick.
+    pub acceleration: f32,
+    pub strategy: ChaseStrategy,
+}
+
+impl Predator {
+    pub fn new(position: Vec2, strategy: ChaseStrategy) -> Self {
+        Predator {
+            position,
+            velocity: Vec2::ZERO,
+            max_speed: 130.0,
+            min_speed: 20.0,
+            acceleration: 0.05,
+            strategy,
+        }
+    }
+}
+
+pub fn spawn_predator(commands: &mut Commands, position: Vec2, strategy: ChaseStrategy) -> Entity {
+    commands
+        .spawn((
+            Name::new("predator"),
+            // above the boids, whose z grows with the entity index
+            Transform::from_xyz(position.x, position.y, 10.0),
+            PreviousPosition(position),
+            Predator::new(position, strategy),
+        ))
+        .id()
+}
+
+/// Where a predator at `position` should head. `predators` is how many predators are in the
+/// index, so the nearest search can look past them. Stragglers are counted against their own
+/// species.
+pub fn chase_target<I: SpatialIndex<EntityWrapper>>(
+    index: &I,
+    position: Vec2,
+    strategy: ChaseStrategy,
+    centroid: Option<Vec2>,
+    predators: usize,
+    config: &BoidConfiguration,
+) -> Option<Vec2> {
+    let wrap = config.wrap_bounds();
+    let nearest = || {
+        index
+            .k_nearest_with_wrap(position, predators + 1, wrap)
+            .into_iter()
+            .find(|(_, other)| !other.predator)
+            .map(|(point, _)| point)
+    };
+
+    match strategy {
+        ChaseStrategy::Nearest => nearest(),
+        ChaseStrategy::Centroid => centroid,
+        ChaseStrategy::Straggler => {
+            let mut candidates = vec![];
+            index.query_radius_with_wrap(
+                position,
+                config.predator_hunt_range,
+                wrap,
+                |point, other| {
+                    if !other.predator {
+                        candidates.push((point, other.entity, other.species));
+                    }
+                },
+            );
+
+            candidates
+                .into_iter()
+                .map(|(point, entity, species)| {
+                    let visible_range = config
+                        .species
+                        .get(species)
+                        .map_or(0.0, |species| species.visible_range);
+
+                    let mut neighbors = 0;
+                    index.query_radius_with_wrap(point, visible_range, wrap, |_, other| {
+                        if !other.predator && other.species == species && other.entity != entity {
+                            neighbors += 1;
+                        }
+                    });
+                    (neighbors, point.distance_squared(position), point)
+                })
+                .min_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)))
+                .map(|(_, _, point)| point)
+                .or_else(nearest)
+        }
+    }
+}
+
+/// Steers predators towards their targets and, when `predator_kills` is on, despawns the boids
+/// they catch. Unless `predator_respawn_kills` is set each kill also lowers its species'
+/// `spawn_count`, so `boid_ensure_count` doesn't put the boid straight back.
+/// The centre of `positions` as seen from `from`. With `wrap` set each position counts at its
+/// nearest image, so a flock split by a seam isn't averaged into the middle of the screen.
+pub fn centroid_from(
+    wrap: Option<Rect>,
+    from: Vec2,
+    positions: impl Iterator<Item = Vec2>,
+) -> Option<Vec2> {
+    let mut count = 0;
+    let mut sum = Vec2::ZERO;
+    for position in positions {
+        sum += boundary::offset(wrap, from, position);
+        count += 1;
+    }
+    (count > 0).then(|| from + sum / count as f32)
+}
+
+pub fn predator_chase<I: SpatialIndex<EntityWrapper> + Resource>(
+    mut commands: Commands,
+    mut predators: Query<&mut Predator>,
+    boids: Query<(&Boid, &Species)>,
+    index: Res<I>,
+    mut config: Query<&mut BoidConfiguration>,
+    mut killed: Local<HashSet<Entity>>,
+) {
+    if predators.is_empty() {
+        return;
+    }
+
+    let mut config = config.single_mut();
+
+    let wrap = config.wrap_bounds();
+    let predator_count = predators.iter().count();
+
+    killed.clear();
+
+    for mut predator in predators.iter_mut() {
+        let position = predator.position;
+
+        let centroid = (predator.strategy == ChaseStrategy::Centroid)
+            .then(|| centroid_from(wrap, position, boids.iter().map(|(boid, _)| boid.position)))
+            .flatten();
+        let target = chase_target(
+            index.as_ref(),
+            position,
+            predator.strategy,
+            centroid,
+            predator_count,
+            &config,
+        );
+
+        if let Some(target) = target {
+            let desired =
+                boundary::offset(wrap, position, target).normalize_or_zero() * predator.max_speed;
+            let steer = (desired - predator.velocity) * predator.acceleration;
+            predator.velocity += steer;
+        }
+
+        if config.predator_kills {
+            index.query_radius_with_wrap(position, config.predator_kill_range, wrap, |_, other| {
+                if !other.predator && killed.insert(other.entity) {
+                    commands.entity(other.entity).despawn_recursive();
+                }
+            });
+        }
+    }
+
+    if !config.predator_respawn_kills {
+        for (_, species) in killed.iter().filter_map(|entity| boids.get(*entity).ok()) {
+            if let Some(species) = config.species.get_mut(species.0) {
+                species.spawn_count = species.spawn_count.saturating_sub(1);
+            }
+        }
+    }
+}
+
+pub fn predator_movement(
+    time: Res<Time>,
+    mut predators: Query<(&mut Predator, &mut PreviousPosition)>,
+    config: Query<&BoidConfiguration>,
+) {
+    let config = config.single();
+
+    for (mut predator, mut previous) in predators.iter_mut() {
+        let steer = boundary::steer(config, predator.position);
+        predator.velocity += steer;
+
+        predator.velocity = predator
+            .velocity
+            .clamp_length(predator.min_speed, predator.max_speed);
+
+        previous.0 = predator.position;
+        let velocity = predator.velocity;
+        predator.position += velocity * time.delta().as_secs_f32();
+
+        let Predator {
+            position, velocity, ..
+        } = &mut *predator;
+        previous.0 += boundary::confine(config, position, velocity);
+    }
+}
+
+/// The predator section of `boids_ui`.
+#[derive(SystemParam)]
+pub struct PredatorEditor<'w, 's> {
+    commands: Commands<'w, 's>,
+    predators: Query<'w, 's, (Entity, &'static Predator)>,
+    strategy: Local<'s, ChaseStrategy>,
+}
+
+impl PredatorEditor<'_, '_> {
+    pub fn ui(&mut self, ui: &mut egui::Ui, config: &BoidConfiguration, rng: &mut impl Rng) {
+        ui.horizontal(|ui| {
+            let strategy = &mut *self.strategy;
+            ui.radio_value(strategy, ChaseStrategy::Nearest, "Nearest");
+            ui.radio_value(strategy, ChaseStrategy::Centroid, "Centroid");
+            ui.radio_value(strategy, ChaseStrategy::Straggler, "Straggler");
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("add predator").clicked() {
+                let bounds = config.boid_bounds;
+                let position = Vec2::new(
+                    lerp(bounds.min.x..=bounds.max.x, rng.random::<f32>()),
+                    lerp(bounds.min.y..=bounds.max.y, rng.random::<f32>()),
+                );
+
+                spawn_predator(&mut self.commands, position, *self.strategy);
+            }
+
+            if ui.button("clear").clicked() {
+                for (entity, _) in self.predators.iter() {
+                    self.commands.entity(entity).despawn_recursive();
+                }
+            }
+        });
+
+        egui::Grid::new("predators").show(ui, |ui| {
+            for (entity, predator) in self.predators.iter() {
+                ui.label(format!("{:?}", predator.strategy));
+                ui.label(format!(
+                    "({:.0}, {:.0})",
+                    predator.position.x, predator.position.y
+                ));
+                if ui.button("remove").clicked() {
+                    self.commands.entity(entity).despawn_recursive();
+                }
+                ui.end_row();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::prelude::*;
+
+    use crate::config::{BoidConfiguration, SpeciesConfig};
+    use crate::flocking::EntityWrapper;
+    use crate::predator::{centroid_from, chase_target, ChaseStrategy};
+    use crate::spatial::SpatialIndex;
+    use crate::uniform_grid::UniformGrid;
+
+    fn point(index: u32, position: Vec2, predator: bool) -> (Vec2, EntityWrapper) {
+        (
+            position,
+            EntityWrapper {
+                entity: Entity::from_raw(index),
+                velocity: Vec2::ZERO,
+                species: 0,
+                predator,
+            },
+        )
+    }
+
+    #[test]
+    fn chase_targets() {
+        let config = BoidConfiguration {
+            species: vec![SpeciesConfig {
+                visible_range: 20.0,
+                ..default()
+            }],
+            predator_hunt_range: 200.0,
+            ..default()
+        };
+
+        let mut grid = UniformGrid::new(Rect::new(-200.0, -200.0, 200.0, 200.0), 20.0);
+        grid.build([
+            point(0, Vec2::ZERO, true),
+            // a tight group close by
+            point(1, Vec2::new(30.0, 0.0), false),
+            point(2, Vec2::new(35.0, 0.0), false),
+            point(3, Vec2::new(30.0, 5.0), false),
+            // a loner further off
+            point(4, Vec2::new(-100.0, 0.0), false),
+        ]);
+
+        let target =
+            |strategy, centroid| chase_target(&grid, Vec2::ZERO, strategy, centroid, 1, &config);
+
+        assert_eq!(
+            target(ChaseStrategy::Nearest, None),
+            Some(Vec2::new(30.0, 0.0)),
+            "skips the predator itself"
+        );
+        assert_eq!(
+            target(ChaseStrategy::Straggler, None),
+            Some(Vec2::new(-100.0, 0.0))
+        );
+        assert_eq!(
+            target(ChaseStrategy::Centroid, Some(Vec2::ONE)),
+            Some(Vec2::ONE)
+        );
+    }
+
+    #[test]
+    fn centroid_across_the_seam() {
+        let flock = [Vec2::new(95.0, 0.0), Vec2::new(-95.0, 0.0)];
+        let from = Vec2::new(90.0, 0.0);
+
+        assert_eq!(
+            centroid_from(None, from, flock.into_iter()),
+            Some(Vec2::ZERO)
+        );
+        // wrapped, the two boids sit either side of the seam at x = 100
+        let wrap = Some(Rect::new(-100.0, -100.0, 100.0, 100.0));
+        assert_eq!(
+            centroid_from(wrap, from, flock.into_iter()),
+            Some(Vec2::new(100.0, 0.0))
+        );
+        assert_eq!(centroid_from(wrap, from, std::iter::empty()), None);
+    }
+}
@@ -0,0 +1,348 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_egui::egui;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Deserializer};
+
+use crate::config::BoidConfiguration;
+
+/// Where presets are read from and saved to, relative to the working directory.
+pub const PRESET_DIR: &str = "assets/presets";
+
+/// Presets compiled into the binary so they are there without the assets directory, and on the
+/// web. A file with the same name in `PRESET_DIR` takes precedence.
+pub const BUILT_IN: [(&str, &str); 3] = [
+    (
+        "tight_murmuration",
+        include_str!("../assets/presets/tight_murmuration.ron"),
+    ),
+    (
+        "loose_swarm",
+        include_str!("../assets/presets/loose_swarm.ron"),
+    ),
+    (
+        "fish_school",
+        include_str!("../assets/presets/fish_school.ron"),
+    ),
+];
+
+#[derive(Debug)]
+pub enum PresetError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+    NotFound(String),
+    /// Names may only use letters, digits, `_` and `-`, so they can't point outside
+    /// `PRESET_DIR`.
+    InvalidName(String),
+}
+
+impl fmt::Display for PresetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PresetError::Io(error) => write!(f, "{}", error),
+            PresetError::Parse(error) => write!(f, "{}", error),
+            PresetError::Serialize(error) => write!(f, "{}", error),
+            PresetError::NotFound(name) => write!(f, "no preset named {:?}", name),
+            PresetError::InvalidName(name) => write!(
+                f,
+                "{:?} is not a preset name, use letters, digits, _ and -",
+                name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PresetError {}
+
+/// A loaded preset.
+pub struct Preset {
+    pub config: BoidConfiguration,
+    /// The seed, if the file gives one. `config.seed` is 0 when it doesn't.
+    pub seed: Option<u64>,
+}
+
+pub fn to_ron(config: &BoidConfiguration) -> Result<String, PresetError> {
+    ron::ser::to_string_pretty(config, PrettyConfig::default()).map_err(PresetError::Serialize)
+}
+
+pub fn from_ron(text: &str) -> Result<Preset, PresetError> {
+    // a second pass over just the seed tells a missing seed apart from a seed of 0
+    #[derive(Deserialize)]
+    struct Seed {
+        #[serde(default, deserialize_with = "present")]
+        seed: Option<u64>,
+    }
+
+    fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+        u64::deserialize(deserializer).map(Some)
+    }
+
+    let config = ron::from_str(text).map_err(PresetError::Parse)?;
+    let Seed { seed } = ron::from_str(text).map_err(PresetError::Parse)?;
+    Ok(Preset { config, seed })
+}
+
+/// Replaces the tuning in `config` with the preset's, keeping the bounds, and the seed unless the
+/// preset sets one.
+pub fn apply(config: &mut BoidConfiguration, preset: Preset) {
+    let total_boids = config.total_boids;
+    let spawn_range = config.spawn_range;
+    let boid_bounds = config.boid_bounds;
+    let seed = preset.seed.unwrap_or(config.seed);
+
+    *config = BoidConfiguration {
+        total_boids,
+        spawn_range,
+        boid_bounds,
+        seed,
+        ..preset.config
+    };
+    config.fill_interactions();
+    config.enforce_limits();
+}
+
+fn preset_path(name: &str) -> Result<PathBuf, PresetError> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid {
+        return Err(PresetError::InvalidName(name.to_string()));
+    }
+
+    Ok(PathBuf::from(PRESET_DIR).join(format!("{}.ron", name)))
+}
+
+/// Names of the built-in presets and any `.ron` files in `PRESET_DIR`, sorted.
+pub fn list_presets() -> Vec<String> {
+    let mut names = BUILT_IN
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .collect::<Vec<_>>();
+
+    if let Ok(entries) = std::fs::read_dir(PRESET_DIR) {
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension().is_some_and(|extension| extension == "ron") {
+                if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    names.push(stem.to_string());
+                }
+            }
+        }
+    }
+
+    names.sort();
+    names.dedup();
+    names
+}
+
+pub fn load_preset(name: &str) -> Result<Preset, PresetError> {
+    match std::fs::read_to_string(preset_path(name)?) {
+        Ok(text) => from_ron(&text),
+        Err(error) => match BUILT_IN.iter().find(|(built_in, _)| *built_in == name) {
+            Some((_, text)) => from_ron(text),
+            None if error.kind() == std::io::ErrorKind::NotFound => {
+                Err(PresetError::NotFound(name.to_string()))
+            }
+            None => Err(PresetError::Io(error)),
+        },
+    }
+}
+
+/// Reads a preset from any path, rather than by name from `PRESET_DIR`.
+pub fn load_file(path: &Path) -> Result<Preset, PresetError> {
+    let text = std::fs::read_to_string(path).map_err(PresetError::Io)?;
+    from_ron(&text)
+}
+
+/// Saves `config` as `name` in `PRESET_DIR`, returning the path written.
+pub fn save_preset(name: &str, config: &BoidConfiguration) -> Result<PathBuf, PresetError> {
+    let path = preset_path(name)?;
+    let text = to_ron(config)?;
+    std::fs::create_dir_all(PRESET_DIR).map_err(PresetError::Io)?;
+    std::fs::write(&path, text).map_err(PresetError::Io)?;
+    Ok(path)
+}
+
+pub struct PresetState {
+    name: String,
+    presets: Vec<String>,
+    status: Option<String>,
+}
+
+impl Default for PresetState {
+    fn default() -> Self {
+        PresetState {
+            name: BUILT_IN[0].0.to_string(),
+            presets: list_presets(),
+            status: None,
+        }
+    }
+}
+
+/// The preset section of `boids_ui`.
+#[derive(SystemParam)]
+pub struct PresetEditor<'s> {
+    state: Local<'s, PresetState>,
+}
+
+impl PresetEditor<'_> {
+    pub fn ui(&mut self, ui: &mut egui::Ui, config: &mut BoidConfiguration) {
+        let state = &mut *self.state;
+
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("presets")
+                .selected_text(state.name.clone())
+                .show_ui(ui, |ui| {
+                    for preset in state.presets.iter() {
+                        ui.selectable_value(&mut state.name, preset.clone(), preset);
+                    }
+                });
+
+            ui.text_edit_singleline(&mut state.name);
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("load").clicked() {
+                state.status = match load_preset(&state.name) {
+                    Ok(preset) => {
+                        apply(config, preset);
+                        Some(format!("loaded {}", state.name))
+                    }
+                    Err(error) => Some(error.to_string()),
+                };
+            }
+
+            if ui.button("save").clicked() {
+                state.status = match save_preset(&state.name, config) {
+                    Ok(path) => {
+                        state.presets = list_presets();
+                        Some(format!("saved {}", path.display()))
+                    }
+                    Err(error) => Some(error.to_string()),
+                };
+            }
+
+            if ui.button("refresh").clicked() {
+                state.presets = list_presets();
+            }
+        });
+
+        if let Some(status) = &state.status {
+            ui.label(status);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::prelude::*;
+
+    use crate::config::{BoidConfiguration, NeighborMode, SpeciesConfig, MIN_SPATIAL_HASH_SIZE};
+    use crate::preset::{
+        apply, from_ron, load_preset, save_preset, to_ron, Preset, PresetError, BUILT_IN,
+    };
+
+    #[test]
+    fn round_trip() {
+        let mut config = BoidConfiguration {
+            neighbor_mode: NeighborMode::Hybrid { k: 5 },
+            view_angle: 200.0,
+            ..default()
+        };
+        config.add_species(SpeciesConfig {
+            name: "other".to_string(),
+            ..default()
+        });
+
+        let loaded = from_ron(&to_ron(&config).unwrap()).unwrap().config;
+        assert_eq!(loaded.neighbor_mode, NeighborMode::Hybrid { k: 5 });
+        assert_eq!(loaded.view_angle, 200.0);
+        assert_eq!(loaded.species.len(), 2);
+        assert_eq!(loaded.interactions, config.interactions);
+    }
+
+    #[test]
+    fn built_in_presets_parse() {
+        for (name, text) in BUILT_IN {
+            let preset = from_ron(text).unwrap_or_else(|error| panic!("{}: {}", name, error));
+
+            let mut config = BoidConfiguration {
+                boid_bounds: Rect::new(-1.0, -2.0, 3.0, 4.0),
+                ..default()
+            };
+            apply(&mut config, preset);
+            assert_eq!(
+                config.boid_bounds,
+                Rect::new(-1.0, -2.0, 3.0, 4.0),
+                "{} keeps the bounds",
+                name
+            );
+            assert_eq!(config.interactions.len(), config.species.len());
+        }
+    }
+
+    #[test]
+    fn limits_the_spatial_hash_size() {
+        let mut config = BoidConfiguration::default();
+        apply(
+            &mut config,
+            Preset {
+                config: BoidConfiguration {
+                    spatial_hash_size: 0,
+                    tick_rate: f64::NAN,
+                    ..default()
+                },
+                seed: None,
+            },
+        );
+        assert_eq!(config.spatial_hash_size, MIN_SPATIAL_HASH_SIZE);
+        assert_eq!(config.tick_rate, BoidConfiguration::default().tick_rate);
+    }
+
+    #[test]
+    fn keeps_the_seed_unless_given() {
+        let mut config = BoidConfiguration {
+            seed: 42,
+            ..default()
+        };
+
+        let (_, text) = BUILT_IN[0];
+        let preset = from_ron(text).unwrap();
+        assert_eq!(preset.seed, None);
+        apply(&mut config, preset);
+        assert_eq!(config.seed, 42, "built-in presets have no seed");
+
+        let seeded = to_ron(&BoidConfiguration {
+            seed: 0,
+            ..default()
+        })
+        .unwrap();
+        let preset = from_ron(&seeded).unwrap();
+        assert_eq!(preset.seed, Some(0));
+        apply(&mut config, preset);
+        assert_eq!(config.seed, 0, "an explicit seed, even 0, is used");
+    }
+
+    #[test]
+    fn rejects_names_outside_the_preset_dir() {
+        for name in ["../../escape", "a/b", "..", "", "name.ron"] {
+            assert!(
+                matches!(load_preset(name), Err(PresetError::InvalidName(_))),
+                "{:?}",
+                name
+            );
+            assert!(
+                matches!(
+                    save_preset(name, &BoidConfiguration::default()),
+                    Err(PresetError::InvalidName(_))
+                ),
+                "{:?}",
+                name
+            );
+        }
+    }
+}
@@ -5,7 +5,7 @@ use bevy::{
     math::Isometry2d,
 };
 
-use crate::{config::BoidConfiguration, QuadtreeJail};
+use crate::{config::BoidConfiguration, flocking::QuadtreeJail};
 
 pub fn render_quadtree(
     config: Query<&BoidConfiguration>,
@@ -1,13 +1,36 @@
 pub mod gizmos;
 
+use std::hash::Hash;
+
 use bevy::math::{Rect, Vec2};
+use bevy::utils::hashbrown::HashMap;
+
+use crate::spatial::{k_nearest_by_radius, SpatialIndex};
 
+/// Depth used when none is given. Nodes at this depth never split, so any number of identical
+/// points fit in one leaf.
+pub const DEFAULT_MAX_DEPTH: usize = 12;
+
+/// A point quadtree that keeps every point in a leaf and tracks them by key, so points can be
+/// moved or removed without rebuilding the tree.
 #[derive(Debug)]
-pub struct Quadtree<T: Clone + std::fmt::Debug> {
-    boundary: Rect,
+pub struct Quadtree<K, T> {
+    root: Node<K, T>,
     capacity: usize,
-    points: Vec<(Vec2, T)>,
-    quadrants: Option<Quadrants<Box<Quadtree<T>>>>,
+    max_depth: usize,
+    // where each key is stored, and the build generation it was last seen in
+    locations: HashMap<K, (Vec2, u32)>,
+    generation: u32,
+}
+
+#[derive(Debug)]
+struct Node<K, T> {
+    boundary: Rect,
+    depth: usize,
+    // only leaves hold points
+    points: Vec<(K, Vec2, T)>,
+    quadrants: Option<Box<Quadrants<Node<K, T>>>>,
+    // number of points in this subtree
     count: usize,
 }
 
@@ -18,47 +41,63 @@ impl<T> Quadrants<T> {
     fn nw(&self) -> &T {
         &self.0
     }
-    fn nw_mut(&mut self) -> &mut T {
-        &mut self.0
-    }
 
     fn ne(&self) -> &T {
         &self.1
     }
-    fn ne_mut(&mut self) -> &mut T {
-        &mut self.1
-    }
 
     fn sw(&self) -> &T {
         &self.2
     }
-    fn sw_mut(&mut self) -> &mut T {
-        &mut self.2
-    }
 
     fn se(&self) -> &T {
         &self.3
     }
-    fn se_mut(&mut self) -> &mut T {
-        &mut self.3
+
+    fn iter(&self) -> impl Iterator<Item = &T> {
+        [self.nw(), self.ne(), self.sw(), self.se()].into_iter()
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        [&mut self.0, &mut self.1, &mut self.2, &mut self.3].into_iter()
     }
 }
 
-impl<T: Clone + std::fmt::Debug> Quadtree<T> {
-    pub fn new(boundary: Rect, capacity: usize) -> Self {
-        Quadtree {
+// squared distance from `point` to the closest point of `boundary`, zero when inside
+fn distance_squared_to(boundary: Rect, point: Vec2) -> f32 {
+    (boundary.min - point)
+        .max(point - boundary.max)
+        .max(Vec2::ZERO)
+        .length_squared()
+}
+
+fn contains(boundary: Rect, point: Vec2) -> bool {
+    boundary.min.x <= point.x
+        && point.x < boundary.max.x
+        && boundary.min.y <= point.y
+        && point.y < boundary.max.y
+}
+
+impl<K: Copy + Eq, T> Node<K, T> {
+    fn new(boundary: Rect, depth: usize) -> Self {
+        Node {
             boundary,
-            capacity,
+            depth,
             points: Vec::new(),
             quadrants: None,
             count: 0,
         }
     }
 
-    fn subdivide(&mut self) {
+    fn mid(&self) -> Vec2 {
+        self.boundary.min + (self.boundary.max - self.boundary.min) / 2.0
+    }
+
+    fn subdivide(&mut self, capacity: usize, max_depth: usize) {
         let min = self.boundary.min;
         let max = self.boundary.max;
-        let mid = self.boundary.min + (max - min) / 2.0;
+        let mid = self.mid();
+        let depth = self.depth + 1;
 
         let nw_boundary = Rect {
             min: Vec2::new(min.x, mid.y),
@@ -77,108 +116,450 @@ impl<T: Clone + std::fmt::Debug> Quadtree<T> {
             max: Vec2::new(max.x, mid.y),
         };
 
-        self.quadrants = Some(Quadrants(
-            Box::new(Quadtree::new(nw_boundary, self.capacity)),
-            Box::new(Quadtree::new(ne_boundary, self.capacity)),
-            Box::new(Quadtree::new(sw_boundary, self.capacity)),
-            Box::new(Quadtree::new(se_boundary, self.capacity)),
+        let mut quadrants = Box::new(Quadrants(
+            Node::new(nw_boundary, depth),
+            Node::new(ne_boundary, depth),
+            Node::new(sw_boundary, depth),
+            Node::new(se_boundary, depth),
         ));
+
+        for (key, point, value) in self.points.drain(..) {
+            let child = Self::child_for(&mut quadrants, self.boundary, point);
+            child.count += 1;
+            child.points.push((key, point, value));
+        }
+
+        // every point may have landed in the same child
+        for child in quadrants.iter_mut() {
+            if child.points.len() > capacity && child.depth < max_depth {
+                child.subdivide(capacity, max_depth);
+            }
+        }
+
+        self.quadrants = Some(quadrants);
     }
 
-    fn contains(&self, point: Vec2) -> bool {
-        self.boundary.min.x <= point.x
-            && point.x < self.boundary.max.x
-            && self.boundary.min.y <= point.y
-            && point.y < self.boundary.max.y
+    // same split as `subdivide`, so the chosen child always contains the point
+    fn child_for(
+        quadrants: &mut Quadrants<Node<K, T>>,
+        boundary: Rect,
+        point: Vec2,
+    ) -> &mut Node<K, T> {
+        let mid = boundary.min + (boundary.max - boundary.min) / 2.0;
+        match (point.x < mid.x, point.y < mid.y) {
+            (true, false) => &mut quadrants.0,
+            (false, false) => &mut quadrants.1,
+            (true, true) => &mut quadrants.2,
+            (false, true) => &mut quadrants.3,
+        }
     }
 
-    pub fn insert(&mut self, point: Vec2, value: T) {
-        if !self.contains(point) {
-            return;
+    fn insert(&mut self, key: K, point: Vec2, value: T, capacity: usize, max_depth: usize) {
+        self.count += 1;
+
+        match &mut self.quadrants {
+            Some(quadrants) => {
+                Self::child_for(quadrants, self.boundary, point)
+                    .insert(key, point, value, capacity, max_depth);
+            }
+            None => {
+                self.points.push((key, point, value));
+                if self.points.len() > capacity && self.depth < max_depth {
+                    self.subdivide(capacity, max_depth);
+                }
+            }
         }
+    }
 
-        if self.points.len() < self.capacity {
-            self.count += 1;
-            self.points.push((point, value.clone()));
-        } else {
-            if self.quadrants.is_none() {
-                self.subdivide();
+    fn remove(&mut self, key: K, point: Vec2, capacity: usize) -> Option<T> {
+        let removed = match &mut self.quadrants {
+            Some(quadrants) => {
+                Self::child_for(quadrants, self.boundary, point).remove(key, point, capacity)
             }
+            None => {
+                let index = self.points.iter().position(|(k, _, _)| *k == key)?;
+                Some(self.points.swap_remove(index).2)
+            }
+        };
 
-            if let Some(quadrant) = &mut self.quadrants {
-                quadrant.nw_mut().insert(point, value.clone());
-                quadrant.ne_mut().insert(point, value.clone());
-                quadrant.sw_mut().insert(point, value.clone());
-                quadrant.se_mut().insert(point, value.clone());
+        if removed.is_some() {
+            self.count -= 1;
+            if self.count <= capacity {
+                self.collapse();
             }
         }
+
+        removed
     }
 
-    #[allow(dead_code)]
-    pub fn get_count(&self) -> usize {
-        self.count
-            + match &self.quadrants {
-                None => 0,
-                Some(quadrants) => {
-                    quadrants.0.get_count()
-                        + quadrants.1.get_count()
-                        + quadrants.2.get_count()
-                        + quadrants.3.get_count()
-                }
+    // pull the points of all children back up into this node and make it a leaf again
+    fn collapse(&mut self) {
+        if let Some(mut quadrants) = self.quadrants.take() {
+            for child in quadrants.iter_mut() {
+                child.collapse();
+                self.points.append(&mut child.points);
             }
+        }
     }
 
-    pub fn query(&self, range: Rect) -> Vec<(Vec2, T)> {
-        let mut result = vec![];
-        self.query_internal(range, &mut result);
-        result
+    fn leaf_mut(&mut self, point: Vec2) -> &mut Node<K, T> {
+        if self.quadrants.is_none() {
+            return self;
+        }
+
+        let boundary = self.boundary;
+        let quadrants = self.quadrants.as_mut().expect("checked above");
+        Self::child_for(quadrants, boundary, point).leaf_mut(point)
     }
 
-    // Function to query points within a range
-    fn query_internal(&self, range: Rect, found_points: &mut Vec<(Vec2, T)>) {
-        if self.boundary.intersect(range).is_empty() {
+    fn query_internal(&self, range: Rect, found_points: &mut Vec<(Vec2, T)>)
+    where
+        T: Clone,
+    {
+        if self.count == 0 || self.boundary.intersect(range).is_empty() {
             return;
         }
 
-        if self.points.len() > 0 {
-            for (point, data) in self.points.iter().cloned() {
-                if range.contains(point) {
-                    found_points.push((point, data));
+        for (_, point, data) in self.points.iter() {
+            if range.contains(*point) {
+                found_points.push((*point, data.clone()));
+            }
+        }
+
+        if let Some(quadrants) = &self.quadrants {
+            for child in quadrants.iter() {
+                child.query_internal(range, found_points);
+            }
+        }
+    }
+
+    fn for_each_in_radius<'a>(
+        &'a self,
+        center: Vec2,
+        radius_squared: f32,
+        visit: &mut impl FnMut(Vec2, &'a T),
+    ) -> usize {
+        if self.count == 0 || distance_squared_to(self.boundary, center) > radius_squared {
+            return 0;
+        }
+
+        for (_, point, data) in self.points.iter() {
+            if point.distance_squared(center) <= radius_squared {
+                visit(*point, data);
+            }
+        }
+
+        let mut examined = self.points.len();
+        if let Some(quadrants) = &self.quadrants {
+            for child in quadrants.iter() {
+                examined += child.for_each_in_radius(center, radius_squared, visit);
+            }
+        }
+        examined
+    }
+
+    // `found` is kept sorted nearest first and never grows past `k`. Only points `accept` lets
+    // through are kept, turned into entries by `entry`. Returns how many points were looked at.
+    fn k_nearest_internal<'a, E>(
+        &'a self,
+        center: Vec2,
+        k: usize,
+        accept: &mut impl FnMut(Vec2, &T) -> bool,
+        entry: &impl Fn(&'a T) -> E,
+        found: &mut Vec<(Vec2, E)>,
+    ) -> usize {
+        if self.count == 0 {
+            return 0;
+        }
+
+        if found.len() == k {
+            let furthest = found[k - 1].0.distance_squared(center);
+            if distance_squared_to(self.boundary, center) > furthest {
+                return 0;
+            }
+        }
+
+        for (_, point, data) in self.points.iter() {
+            let distance = point.distance_squared(center);
+            let index =
+                found.partition_point(|(other, _)| other.distance_squared(center) <= distance);
+            if index < k && accept(*point, data) {
+                if found.len() == k {
+                    found.pop();
                 }
+                found.insert(index, (*point, entry(data)));
             }
         }
 
+        let mut examined = self.points.len();
         if let Some(quadrants) = &self.quadrants {
-            quadrants.nw().query_internal(range, found_points);
-            quadrants.ne().query_internal(range, found_points);
-            quadrants.sw().query_internal(range, found_points);
-            quadrants.se().query_internal(range, found_points);
+            // closest children first so the pruning above kicks in sooner
+            let mut children = [
+                quadrants.nw(),
+                quadrants.ne(),
+                quadrants.sw(),
+                quadrants.se(),
+            ];
+            children.sort_by(|a, b| {
+                distance_squared_to(a.boundary, center)
+                    .total_cmp(&distance_squared_to(b.boundary, center))
+            });
+
+            for child in children {
+                examined += child.k_nearest_internal(center, k, accept, entry, found);
+            }
         }
+        examined
     }
 
-    pub fn get_all_bounds(&self) -> Vec<Rect> {
+    fn get_all_bounds(&self, bounds: &mut Vec<Rect>) {
         match &self.quadrants {
-            None => vec![self.boundary],
+            None => bounds.push(self.boundary),
             Some(quadrants) => {
-                let mut bounds = vec![];
-                bounds.extend(quadrants.nw().get_all_bounds());
-                bounds.extend(quadrants.ne().get_all_bounds());
-                bounds.extend(quadrants.sw().get_all_bounds());
-                bounds.extend(quadrants.se().get_all_bounds());
-                bounds
+                for child in quadrants.iter() {
+                    child.get_all_bounds(bounds);
+                }
+            }
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash, T> Quadtree<K, T> {
+    pub fn new(boundary: Rect, capacity: usize) -> Self {
+        Quadtree {
+            root: Node::new(boundary, 0),
+            capacity: capacity.max(1),
+            max_depth: DEFAULT_MAX_DEPTH,
+            locations: HashMap::new(),
+            generation: 0,
+        }
+    }
+
+    /// Limits how deep the tree may split. Leaves at `max_depth` hold any number of points.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn boundary(&self) -> Rect {
+        self.root.boundary
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.locations.contains_key(key)
+    }
+
+    /// Adds a point, replacing any existing point with the same key. Points outside the
+    /// boundary are ignored and `false` is returned.
+    pub fn insert(&mut self, key: K, point: Vec2, value: T) -> bool {
+        if !contains(self.root.boundary, point) {
+            self.remove(key);
+            return false;
+        }
+
+        if let Some(existing) = self.get_mut(key) {
+            *existing = value;
+            return self.update_position(key, point);
+        }
+
+        self.root
+            .insert(key, point, value, self.capacity, self.max_depth);
+        self.locations.insert(key, (point, self.generation));
+        true
+    }
+
+    pub fn remove(&mut self, key: K) -> Option<T> {
+        let (point, _) = self.locations.remove(&key)?;
+        self.root.remove(key, point, self.capacity)
+    }
+
+    /// Moves a point. Stays in place when the point is still inside its leaf, otherwise it is
+    /// reinserted. Returns `false` if the key is unknown or the new position is outside the
+    /// boundary, in which case the point is removed.
+    pub fn update_position(&mut self, key: K, point: Vec2) -> bool {
+        let Some((old_point, _)) = self.locations.get(&key).copied() else {
+            return false;
+        };
+
+        if !contains(self.root.boundary, point) {
+            self.remove(key);
+            return false;
+        }
+
+        let leaf = self.root.leaf_mut(old_point);
+        if contains(leaf.boundary, point) {
+            if let Some(entry) = leaf.points.iter_mut().find(|(k, _, _)| *k == key) {
+                entry.1 = point;
             }
+        } else if let Some(value) = self.root.remove(key, old_point, self.capacity) {
+            self.root
+                .insert(key, point, value, self.capacity, self.max_depth);
         }
+
+        if let Some(location) = self.locations.get_mut(&key) {
+            location.0 = point;
+        }
+        true
+    }
+
+    pub fn get_mut(&mut self, key: K) -> Option<&mut T> {
+        let (point, _) = self.locations.get(&key).copied()?;
+        self.root
+            .leaf_mut(point)
+            .points
+            .iter_mut()
+            .find(|(k, _, _)| *k == key)
+            .map(|(_, _, value)| value)
+    }
+
+    pub fn get_count(&self) -> usize {
+        self.root.count
+    }
+
+    pub fn query(&self, range: Rect) -> Vec<(Vec2, T)>
+    where
+        T: Clone,
+    {
+        let mut result = vec![];
+        self.root.query_internal(range, &mut result);
+        result
+    }
+
+    /// Every point within `radius` of `center`, cloned.
+    pub fn query_radius(&self, center: Vec2, radius: f32) -> Vec<(Vec2, T)>
+    where
+        T: Clone,
+    {
+        let mut result = vec![];
+        self.for_each_in_radius(center, radius, |point, value| {
+            result.push((point, value.clone()))
+        });
+        result
+    }
+
+    /// Calls `visit` for every point within `radius` of `center` without allocating. Nodes
+    /// further than `radius` from `center` are skipped. Returns how many points were in the nodes
+    /// that weren't.
+    pub fn for_each_in_radius<'a>(
+        &'a self,
+        center: Vec2,
+        radius: f32,
+        mut visit: impl FnMut(Vec2, &'a T),
+    ) -> usize {
+        self.root
+            .for_each_in_radius(center, radius * radius, &mut visit)
+    }
+
+    /// The `k` points closest to `center`, nearest first, cloned.
+    pub fn k_nearest(&self, center: Vec2, k: usize) -> Vec<(Vec2, T)>
+    where
+        T: Clone,
+    {
+        let mut found = Vec::with_capacity(k);
+        self.k_nearest_into(center, k, &mut found);
+        found
+            .into_iter()
+            .map(|(point, value)| (point, value.clone()))
+            .collect()
+    }
+
+    /// Fills `found` with the `k` points closest to `center`, nearest first. `found` is cleared
+    /// first; pass the same buffer each time to avoid allocating.
+    pub fn k_nearest_into<'a>(&'a self, center: Vec2, k: usize, found: &mut Vec<(Vec2, &'a T)>) {
+        found.clear();
+        if k == 0 {
+            return;
+        }
+
+        found.reserve(k);
+        self.root
+            .k_nearest_internal(center, k, &mut |_, _| true, &|value| value, found);
+    }
+
+    pub fn get_all_bounds(&self) -> Vec<Rect> {
+        let mut bounds = vec![];
+        self.root.get_all_bounds(&mut bounds);
+        bounds
     }
 
     pub fn clear(&mut self) {
-        self.points.clear();
-        self.quadrants = None;
+        self.root = Node::new(self.root.boundary, 0);
+        self.locations.clear();
+    }
+}
+
+/// Updates the tree in place: existing keys are moved, new keys inserted and keys missing from
+/// `points` removed. Keys come from the values via `From<&T>`.
+impl<K, T> SpatialIndex<T> for Quadtree<K, T>
+where
+    K: Copy + Ord + Hash + for<'a> From<&'a T>,
+    T: Clone,
+{
+    fn build(&mut self, points: impl IntoIterator<Item = (Vec2, T)>) {
+        self.generation = self.generation.wrapping_add(1);
+        let generation = self.generation;
+
+        for (point, value) in points {
+            let key = K::from(&value);
+            if self.insert(key, point, value) {
+                if let Some(location) = self.locations.get_mut(&key) {
+                    location.1 = generation;
+                }
+            }
+        }
+
+        let mut stale: Vec<K> = self
+            .locations
+            .iter()
+            .filter(|(_, (_, seen))| *seen != generation)
+            .map(|(key, _)| *key)
+            .collect();
+        // `locations` iterates in a per-process random order, and removing swaps points around
+        // in their leaves, so remove in key order to keep leaf order the same from run to run
+        stale.sort_unstable();
+
+        for key in stale {
+            self.remove(key);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.get_count()
+    }
+
+    fn query_radius(&self, center: Vec2, radius: f32, visit: impl FnMut(Vec2, &T)) -> usize {
+        self.for_each_in_radius(center, radius, visit)
+    }
+
+    fn k_nearest(&self, center: Vec2, k: usize) -> Vec<(Vec2, T)> {
+        Quadtree::k_nearest(self, center, k)
+    }
+
+    fn k_nearest_filtered(
+        &self,
+        center: Vec2,
+        k: usize,
+        wrap: Option<Rect>,
+        mut accept: impl FnMut(Vec2, &T) -> bool,
+        found: &mut Vec<(Vec2, T)>,
+    ) -> usize {
+        if wrap.is_some() {
+            return k_nearest_by_radius(self, center, k, wrap, accept, found);
+        }
+
+        found.clear();
+        if k == 0 {
+            return 0;
+        }
+        self.root
+            .k_nearest_internal(center, k, &mut accept, &T::clone, found)
     }
 }
 
 #[cfg(test)]
 mod test {
     use crate::quadtree::Quadtree;
+    use crate::spatial::SpatialIndex;
     use bevy::math::{Rect, Vec2};
 
     #[test]
@@ -194,8 +575,8 @@ mod test {
             1,
         );
 
-        q.insert(Vec2 { x: 0.0, y: 0.0 }, 1);
-        q.insert(Vec2 { x: 0.0, y: 1.0 }, 2);
+        q.insert(1, Vec2 { x: 0.0, y: 0.0 }, 1);
+        q.insert(2, Vec2 { x: 0.0, y: 1.0 }, 2);
 
         assert_eq!(q.get_count(), 2, "should have count of 2");
 
@@ -224,7 +605,7 @@ mod test {
         );
 
         for i in 0..50 {
-            q.insert(Vec2::splat(-50.0), i);
+            q.insert(i, Vec2::splat(-50.0), i);
         }
 
         let results = q.query(Rect::new(-51.0, -51.0, -49.0, -49.0));
@@ -232,4 +613,151 @@ mod test {
         assert_eq!(q.get_count(), 50, "count");
         assert_eq!(results.len(), 50, "results");
     }
+
+    fn scattered() -> Quadtree<usize, usize> {
+        let mut q = Quadtree::new(Rect::new(-100.0, -100.0, 100.0, 100.0), 2);
+        for i in 0..300 {
+            let angle = i as f32 * 2.4;
+            let distance = (i % 41) as f32 * 2.3;
+            q.insert(i, Vec2::from_angle(angle) * distance, i);
+        }
+        q
+    }
+
+    #[test]
+    fn query_radius() {
+        let q = scattered();
+        let center = Vec2::new(10.0, -20.0);
+
+        let mut found: Vec<usize> = q
+            .query_radius(center, 30.0)
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect();
+        found.sort();
+
+        let mut expected: Vec<usize> = q
+            .query(Rect::new(-100.0, -100.0, 100.0, 100.0))
+            .into_iter()
+            .filter(|(point, _)| point.distance_squared(center) <= 30.0 * 30.0)
+            .map(|(_, value)| value)
+            .collect();
+        expected.sort();
+
+        assert!(!expected.is_empty());
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn k_nearest() {
+        let q = scattered();
+        let center = Vec2::new(-35.0, 12.0);
+
+        let mut all = q.query(Rect::new(-100.0, -100.0, 100.0, 100.0));
+        all.sort_by(|(a, _), (b, _)| {
+            a.distance_squared(center)
+                .total_cmp(&b.distance_squared(center))
+        });
+
+        let mut found = vec![];
+        q.k_nearest_into(center, 7, &mut found);
+
+        let found: Vec<f32> = found.iter().map(|(p, _)| p.distance(center)).collect();
+        let expected: Vec<f32> = all[..7].iter().map(|(p, _)| p.distance(center)).collect();
+        assert_eq!(found, expected);
+
+        assert_eq!(
+            q.k_nearest(center, 500).len(),
+            300,
+            "k larger than the tree"
+        );
+    }
+
+    #[test]
+    fn remove_and_collapse() {
+        let mut q = Quadtree::new(Rect::new(-100.0, -100.0, 100.0, 100.0), 1);
+
+        for i in 0..8 {
+            q.insert(i, Vec2::new(i as f32 * 10.0 - 40.0, 5.0), i);
+        }
+        assert!(q.get_all_bounds().len() > 1, "should have split");
+
+        for i in 0..7 {
+            assert_eq!(q.remove(i), Some(i));
+        }
+        assert_eq!(q.remove(0), None, "already removed");
+
+        assert_eq!(q.get_count(), 1);
+        assert_eq!(
+            q.get_all_bounds().len(),
+            1,
+            "should collapse back to the root"
+        );
+    }
+
+    #[test]
+    fn update_position_moves_between_leaves() {
+        let mut q = Quadtree::new(Rect::new(-100.0, -100.0, 100.0, 100.0), 1);
+        q.insert(1, Vec2::new(-50.0, -50.0), "a");
+        q.insert(2, Vec2::new(50.0, 50.0), "b");
+
+        assert!(q.update_position(1, Vec2::new(60.0, 60.0)));
+        assert!(q.query(Rect::new(-100.0, -100.0, 0.0, 0.0)).is_empty());
+        assert_eq!(q.query(Rect::new(0.0, 0.0, 100.0, 100.0)).len(), 2);
+
+        assert!(!q.update_position(1, Vec2::new(500.0, 0.0)), "outside");
+        assert_eq!(q.get_count(), 1);
+    }
+
+    #[test]
+    fn incremental_build() {
+        #[derive(Clone, Debug)]
+        struct Point(u32);
+
+        impl From<&Point> for u32 {
+            fn from(point: &Point) -> u32 {
+                point.0
+            }
+        }
+
+        let mut q: Quadtree<u32, Point> = Quadtree::new(Rect::new(-100.0, -100.0, 100.0, 100.0), 2);
+        q.build((0..20).map(|i| (Vec2::new(i as f32 * 5.0 - 50.0, 0.0), Point(i))));
+        assert_eq!(q.get_count(), 20);
+
+        q.build((0..10).map(|i| (Vec2::new(0.0, i as f32 * 5.0 - 50.0), Point(i))));
+        assert_eq!(q.get_count(), 10, "stale keys should be removed");
+        assert_eq!(q.query(Rect::new(-1.0, -51.0, 1.0, 0.0)).len(), 10);
+    }
+
+    #[test]
+    fn removal_order_is_deterministic() {
+        #[derive(Clone, Debug)]
+        struct Point(u32);
+
+        impl From<&Point> for u32 {
+            fn from(point: &Point) -> u32 {
+                point.0
+            }
+        }
+
+        // everything fits in one leaf, so its order is down to the order of removals alone
+        let mut q: Quadtree<u32, Point> =
+            Quadtree::new(Rect::new(-100.0, -100.0, 100.0, 100.0), 64);
+        q.build((0..50).map(|i| (Vec2::new(i as f32, 0.0), Point(i))));
+        q.build(
+            (0..50)
+                .filter(|i| i % 3 != 0)
+                .map(|i| (Vec2::new(i as f32, 0.0), Point(i))),
+        );
+
+        let mut order = vec![];
+        q.for_each_in_radius(Vec2::ZERO, 100.0, |_, value| order.push(value.0));
+
+        let mut expected = (0..50).collect::<Vec<u32>>();
+        for stale in (0..50).filter(|i| i % 3 == 0) {
+            let index = expected.iter().position(|i| *i == stale).unwrap();
+            expected.swap_remove(index);
+        }
+        assert_eq!(order, expected, "stale keys are removed in key order");
+    }
 }
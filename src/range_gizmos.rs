@@ -1,19 +1,26 @@
 use bevy::{color::Color, ecs::system::Query, gizmos::gizmos::Gizmos};
 
-use crate::{config::BoidConfiguration, Boid};
+use crate::{
+    boid::{Boid, Species},
+    config::BoidConfiguration,
+};
 
 pub fn boid_draw_range_gizmos(
     mut gizmos: Gizmos,
-    boids: Query<&Boid>,
+    boids: Query<(&Boid, &Species)>,
     config: Query<&BoidConfiguration>,
 ) {
     let config = config.single();
 
-    for boid in boids.iter() {
+    for (boid, species) in boids.iter() {
+        let Some(species) = config.species.get(species.0) else {
+            continue;
+        };
+
         if config.protected_range_gizmo.enabled {
             gizmos.circle_2d(
                 boid.position,
-                config.protected_range,
+                species.protected_range,
                 Color::linear_rgba(
                     config.protected_range_gizmo.color_rgba[0],
                     config.protected_range_gizmo.color_rgba[1],
@@ -26,7 +33,7 @@ pub fn boid_draw_range_gizmos(
         if config.visible_range_gizmo.enabled {
             gizmos.circle_2d(
                 boid.position,
-                config.visible_range,
+                species.visible_range,
                 Color::linear_rgba(
                     config.protected_range_gizmo.color_rgba[0],
                     config.protected_range_gizmo.color_rgba[1],
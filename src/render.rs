@@ -0,0 +1,215 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use rand::Rng;
+
+use crate::boid::{Boid, PreviousPosition, Species};
+use crate::config::{BoidConfiguration, BoidShape, BoundaryMode, ColorType};
+use crate::highlight::HighlightedNeighbor;
+use crate::predator::Predator;
+use crate::rng::BoidRng;
+
+#[derive(Component)]
+pub struct BoidVisualData {
+    pub shapes: HashMap<BoidShape, Handle<Mesh>>,
+    pub predator_shape: Handle<Mesh>,
+    pub predator_material: Handle<ColorMaterial>,
+}
+
+pub fn setup_visuals(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let triangle = |size: f32| {
+        Triangle2d::new(
+            Vec2::Y * size,
+            Vec2::new(-size / 2.0, -size),
+            Vec2::new(size / 2.0, -size),
+        )
+    };
+
+    let shapes = BoidShape::ALL
+        .into_iter()
+        .map(|shape| {
+            let mesh = match shape {
+                BoidShape::Triangle => meshes.add(triangle(10.0)),
+                BoidShape::Dart => meshes.add(Triangle2d::new(
+                    Vec2::Y * 12.0,
+                    Vec2::new(-3.0, -10.0),
+                    Vec2::new(3.0, -10.0),
+                )),
+                BoidShape::Circle => meshes.add(Circle::new(5.0)),
+            };
+            (shape, mesh)
+        })
+        .collect();
+    let predator_shape = meshes.add(triangle(20.0));
+    let predator_material = materials.add(Color::srgb(1.0, 0.2, 0.1));
+
+    commands.spawn_empty().insert(BoidVisualData {
+        shapes,
+        predator_shape,
+        predator_material,
+    });
+}
+
+// Give newly spawned boids their species' mesh and their own material, and swap the mesh when
+// the species shape changes
+pub fn boid_attach_visuals(
+    mut commands: Commands,
+    bvd: Query<&BoidVisualData>,
+    boids: Query<(Entity, &Boid, &Species, Option<&Mesh2d>)>,
+    config: Query<&BoidConfiguration>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let bvd = bvd.single();
+    let config = config.single();
+
+    for (entity, boid, species, mesh) in boids.iter() {
+        let shape = config
+            .species
+            .get(species.0)
+            .map(|species| species.shape)
+            .unwrap_or_default();
+        let handle = &bvd.shapes[&shape];
+
+        match mesh {
+            None => {
+                commands.entity(entity).insert((
+                    Mesh2d(handle.clone()),
+                    MeshMaterial2d(materials.add(boid.initial_color)),
+                ));
+            }
+            Some(mesh) if mesh.0 != *handle => {
+                commands.entity(entity).insert(Mesh2d(handle.clone()));
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+// Predators all share one mesh and material
+pub fn predator_attach_visuals(
+    mut commands: Commands,
+    bvd: Query<&BoidVisualData>,
+    predators: Query<Entity, (With<Predator>, Without<Mesh2d>)>,
+) {
+    let bvd = bvd.single();
+
+    for entity in predators.iter() {
+        commands.entity(entity).insert((
+            Mesh2d(bvd.predator_shape.clone()),
+            MeshMaterial2d(bvd.predator_material.clone()),
+        ));
+    }
+}
+
+pub fn boid_rotation(mut boids: Query<(&Boid, &mut Transform)>) {
+    for (boid, mut transform) in boids.iter_mut() {
+        let angle = boid.velocity.x.atan2(boid.velocity.y);
+        transform.rotation = Quat::from_axis_angle(Vec3::NEG_Z, angle);
+    }
+}
+
+// Update the colors for the boids in the system basedon the configuration
+pub fn boid_update_colors(
+    // only pull in the boids that are not currently highlighted
+    boids: Query<(&Boid, &Species, &MeshMaterial2d<ColorMaterial>), Without<HighlightedNeighbor>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut rng: ResMut<BoidRng>,
+    config: Query<&BoidConfiguration>,
+) {
+    let config = config.single();
+
+    if config.update_color_sample_rate == 0.0 {
+        return;
+    }
+
+    for (boid, species, color) in boids.iter() {
+        let max_speed = config
+            .species
+            .get(species.0)
+            .map_or(1.0, |species| species.max_speed);
+
+        if rng.cosmetic.random::<f32>() <= config.update_color_sample_rate {
+            if let Some(color) = materials.get_mut(color.id()) {
+                match config.update_color_type {
+                    ColorType::Initial => {
+                        color.color = boid.initial_color;
+                    }
+                    ColorType::Synthwave => {
+                        let r: f32 = boid.velocity.x.abs() / max_speed;
+                        let g = boid.velocity.y.abs() / max_speed;
+                        color.color = Color::srgb(r, g, 1.0);
+                    }
+                    ColorType::Pastel => {
+                        let r: f32 = boid.velocity.x.abs() / max_speed;
+                        let g = boid.velocity.y.abs() / max_speed;
+                        color.color = Color::srgb(r, g, (1.0f32 - r - g).clamp(0.0, 1.0));
+                    }
+                    ColorType::PrimaryRGB => {
+                        let r: f32 = (boid.velocity.x + boid.velocity.x.abs()) / max_speed;
+                        let g = (boid.velocity.y + boid.velocity.y.abs()) / max_speed;
+                        color.color = Color::srgb(r, g, (1.0f32 - r - g).clamp(0.0, 1.0));
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Blend between the last two simulation ticks so movement stays smooth at any frame rate
+pub fn update_boids_transform(
+    time: Res<Time<Fixed>>,
+    mut boids: Query<(&Boid, &PreviousPosition, &mut Transform)>,
+) {
+    let alpha = time.overstep_fraction();
+    for (boid, previous, mut transform) in boids.iter_mut() {
+        let position = previous.lerp(boid.position, alpha);
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
+
+pub fn update_predators_transform(
+    time: Res<Time<Fixed>>,
+    mut predators: Query<(&Predator, &PreviousPosition, &mut Transform)>,
+) {
+    let alpha = time.overstep_fraction();
+    for (predator, previous, mut transform) in predators.iter_mut() {
+        let position = previous.lerp(predator.position, alpha);
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+
+        let angle = predator.velocity.x.atan2(predator.velocity.y);
+        transform.rotation = Quat::from_axis_angle(Vec3::NEG_Z, angle);
+    }
+}
+
+pub fn render_bounds_gizmo(config: Query<&BoidConfiguration>, mut gizmos: Gizmos) {
+    let config = config.single();
+
+    if !config.bounds_gizmo.enabled {
+        return;
+    }
+
+    let size = config.boid_bounds.max - config.boid_bounds.min;
+    let position = config.boid_bounds.min + size * 0.5;
+
+    let color = Color::srgba(
+        config.bounds_gizmo.color_rgba[0],
+        config.bounds_gizmo.color_rgba[1],
+        config.bounds_gizmo.color_rgba[2],
+        config.bounds_gizmo.color_rgba[3],
+    );
+
+    match config.boundary_mode {
+        BoundaryMode::Circular => {
+            gizmos.circle_2d(position, size.min_element() / 2.0, color);
+        }
+        BoundaryMode::None => {}
+        _ => {
+            gizmos.rect_2d(Isometry2d::from_translation(position), size, color);
+        }
+    }
+}
@@ -0,0 +1,670 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+
+use crate::boid::{spawn_boid_exact, Boid, PreviousPosition, Species};
+use crate::config::BoidConfiguration;
+use crate::tool::{MouseTool, ToolMode};
+
+/// Bumped whenever `Recording` changes shape.
+pub const RECORDING_VERSION: u32 = 2;
+
+/// Starts every recording file, followed by the bincode encoded `Recording`.
+pub const RECORDING_MAGIC: &[u8; 4] = b"BREC";
+
+/// Where recordings are saved, relative to the working directory.
+pub const RECORDING_DIR: &str = "recordings";
+
+/// Longest recording kept in memory: a minute at the default tick rate, around 140 MB with a
+/// thousand boids. Recording stops and saves once it is reached.
+pub const MAX_RECORDING_FRAMES: usize = 64 * 60;
+
+#[derive(Debug)]
+pub enum RecordingError {
+    Io(std::io::Error),
+    Binary(bincode::Error),
+    /// The file doesn't start with `RECORDING_MAGIC`.
+    NotARecording,
+    Version(u32),
+}
+
+impl fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordingError::Io(error) => write!(f, "{}", error),
+            RecordingError::Binary(error) => write!(f, "{}", error),
+            RecordingError::NotARecording => write!(f, "not a recording"),
+            RecordingError::Version(version) => write!(
+                f,
+                "recording version {} is not supported, expected {}",
+                version, RECORDING_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RecordingError {}
+
+/// Every simulation tick of a run.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Recording {
+    pub version: u32,
+    /// `BoidConfiguration::tick_rate` when recording started, which sets the playback speed.
+    pub tick_rate: f64,
+    pub frames: Vec<RecordedFrame>,
+}
+
+/// The flock at the end of one tick.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    /// The configuration, only on the first frame and on ticks where it changed. `total_boids`
+    /// and the species' `spawn_count` are left at 0, since they follow from `boids`.
+    pub config: Option<BoidConfiguration>,
+    /// The mouse tool, on ticks where it was pushing, spawning or erasing.
+    pub input: Option<RecordedInput>,
+    pub boids: Vec<RecordedBoid>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedBoid {
+    pub species: u16,
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    /// `Boid::initial_color` as linear RGBA.
+    pub initial_color: [f32; 4],
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedInput {
+    /// `ToolMode` as its index in `ToolMode::ALL`.
+    pub mode: u8,
+    pub cursor: [f32; 2],
+    pub brush_radius: f32,
+}
+
+impl Recording {
+    pub fn new(tick_rate: f64) -> Self {
+        Recording {
+            version: RECORDING_VERSION,
+            tick_rate,
+            frames: vec![],
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.frames.len() >= MAX_RECORDING_FRAMES
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, RecordingError> {
+        let mut bytes = RECORDING_MAGIC.to_vec();
+        bincode::serialize_into(&mut bytes, self).map_err(RecordingError::Binary)?;
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RecordingError> {
+        let Some(body) = bytes.strip_prefix(RECORDING_MAGIC) else {
+            return Err(RecordingError::NotARecording);
+        };
+
+        let version = bincode::deserialize::<u32>(body).map_err(RecordingError::Binary)?;
+        if version != RECORDING_VERSION {
+            return Err(RecordingError::Version(version));
+        }
+        bincode::deserialize(body).map_err(RecordingError::Binary)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), RecordingError> {
+        let bytes = self.to_bytes()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(RecordingError::Io)?;
+        }
+        std::fs::write(path, bytes).map_err(RecordingError::Io)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, RecordingError> {
+        Recording::from_bytes(&std::fs::read(path).map_err(RecordingError::Io)?)
+    }
+
+    /// The configuration in effect at `frame`: the last one recorded at or before it.
+    pub fn config_at(&self, frame: usize) -> Option<&BoidConfiguration> {
+        self.frames[..=frame.min(self.frames.len().saturating_sub(1))]
+            .iter()
+            .rev()
+            .find_map(|frame| frame.config.as_ref())
+    }
+}
+
+/// Appends a frame to `recording` every simulation tick while set, up to `MAX_RECORDING_FRAMES`.
+#[derive(Resource, Default)]
+pub struct Recorder {
+    pub recording: Option<Recording>,
+    /// The configuration last stored in `recording`, to compare each tick's against.
+    last_config: Option<BoidConfiguration>,
+}
+
+/// Plays a recording back in place of the simulation. While `recording` is set the `Spawn`,
+/// `Flocking` and `Movement` sets don't run and the boids are placed from the recorded frames.
+#[derive(Resource)]
+pub struct Replay {
+    pub recording: Option<Recording>,
+    /// Position in frames. Fractional so slow playback still moves.
+    pub playhead: f32,
+    pub playing: bool,
+    pub speed: f32,
+    /// The frame the boids were last placed from.
+    shown: Option<usize>,
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        Replay {
+            recording: None,
+            playhead: 0.0,
+            playing: true,
+            speed: 1.0,
+            shown: None,
+        }
+    }
+}
+
+impl Replay {
+    pub fn start(&mut self, recording: Recording) {
+        *self = Replay {
+            recording: Some(recording),
+            ..default()
+        };
+    }
+
+    pub fn frame(&self) -> Option<(usize, &RecordedFrame)> {
+        let recording = self.recording.as_ref()?;
+        let index = (self.playhead as usize).min(recording.frames.len().checked_sub(1)?);
+        Some((index, &recording.frames[index]))
+    }
+}
+
+/// Run condition for the simulation sets.
+pub fn not_replaying(replay: Res<Replay>) -> bool {
+    replay.recording.is_none()
+}
+
+pub fn recorder_capture(
+    mut recorder: ResMut<Recorder>,
+    tool: Res<MouseTool>,
+    config: Query<&BoidConfiguration>,
+    boids: Query<(&Boid, &Species)>,
+) {
+    let Recorder {
+        recording: Some(recording),
+        last_config,
+    } = &mut *recorder
+    else {
+        return;
+    };
+
+    if recording.is_full() {
+        return;
+    }
+
+    // the ui and predator kills touch the config every frame without changing the tuning, so
+    // compare against what was last stored rather than trusting change detection
+    let mut config = config.single().clone();
+    config.total_boids = 0;
+    for species in config.species.iter_mut() {
+        species.spawn_count = 0;
+    }
+    let config =
+        (recording.frames.is_empty() || last_config.as_ref() != Some(&config)).then(|| {
+            *last_config = Some(config.clone());
+            config
+        });
+
+    let input = match (tool.mode, tool.cursor) {
+        (ToolMode::Attract | ToolMode::Repel | ToolMode::Spawn | ToolMode::Erase, Some(cursor))
+            if tool.active =>
+        {
+            Some(RecordedInput {
+                mode: ToolMode::ALL
+                    .iter()
+                    .position(|mode| *mode == tool.mode)
+                    .unwrap_or_default() as u8,
+                cursor: cursor.to_array(),
+                brush_radius: tool.brush_radius,
+            })
+        }
+        _ => None,
+    };
+
+    let boids = boids
+        .iter()
+        .map(|(boid, species)| RecordedBoid {
+            species: species.0 as u16,
+            position: boid.position.to_array(),
+            velocity: boid.velocity.to_array(),
+            initial_color: boid.initial_color.to_linear().to_f32_array(),
+        })
+        .collect();
+
+    recording.frames.push(RecordedFrame {
+        config,
+        input,
+        boids,
+    });
+}
+
+/// Moves the playhead in real time, so pausing the simulation doesn't stop playback.
+pub fn replay_advance(time: Res<Time<Real>>, mut replay: ResMut<Replay>) {
+    let Some(recording) = replay.recording.as_ref() else {
+        return;
+    };
+
+    if !replay.playing {
+        return;
+    }
+
+    let last = recording.frames.len().saturating_sub(1) as f32;
+    let step = time.delta_secs() * replay.speed * recording.tick_rate as f32;
+    replay.playhead = (replay.playhead + step).min(last);
+    if replay.playhead >= last {
+        replay.playing = false;
+    }
+}
+
+type ReplayedBoid = (
+    Entity,
+    &'static mut Boid,
+    &'static mut Species,
+    &'static mut PreviousPosition,
+    Option<&'static MeshMaterial2d<ColorMaterial>>,
+);
+
+/// Places the boids from the frame under the playhead, spawning and despawning to match its
+/// count. `update_boids_transform` and `boid_rotation` then draw them as usual.
+pub fn replay_apply(
+    mut commands: Commands,
+    mut replay: ResMut<Replay>,
+    mut config: Query<&mut BoidConfiguration>,
+    mut boids: Query<ReplayedBoid>,
+    // missing in headless apps
+    mut materials: Option<ResMut<Assets<ColorMaterial>>>,
+) {
+    let Some((index, frame)) = replay.frame() else {
+        return;
+    };
+
+    if replay.shown == Some(index) {
+        return;
+    }
+
+    let recording = replay.recording.as_ref().unwrap();
+    let mut config = config.single_mut();
+    if let Some(recorded) = recording.config_at(index) {
+        let (total_boids, spawn_range, boid_bounds) =
+            (config.total_boids, config.spawn_range, config.boid_bounds);
+        *config = BoidConfiguration {
+            total_boids,
+            spawn_range,
+            boid_bounds,
+            ..recorded.clone()
+        };
+        config.fill_interactions();
+        config.enforce_limits();
+    }
+
+    // leaving the replay carries on from here, so `boid_ensure_count` should keep these
+    for species in config.species.iter_mut() {
+        species.spawn_count = 0;
+    }
+    for boid in frame.boids.iter() {
+        if let Some(species) = config.species.get_mut(boid.species as usize) {
+            species.spawn_count += 1;
+        }
+    }
+
+    let mut existing = boids.iter_mut().collect::<Vec<_>>();
+    existing.sort_by_key(|(entity, ..)| *entity);
+
+    let mut recorded = frame.boids.iter();
+    for (entity, boid, species, previous, material) in existing.iter_mut() {
+        match recorded.next() {
+            Some(state) => {
+                boid.position = Vec2::from_array(state.position);
+                boid.velocity = Vec2::from_array(state.velocity);
+                species.0 = state.species as usize;
+                previous.0 = boid.position;
+
+                let color = LinearRgba::from_f32_array(state.initial_color).into();
+                if boid.initial_color != color {
+                    boid.initial_color = color;
+                    // the material was made from the old color, so don't wait for a resample
+                    if let (Some(material), Some(materials)) = (material, materials.as_mut()) {
+                        if let Some(material) = materials.get_mut(material.id()) {
+                            material.color = color;
+                        }
+                    }
+                }
+            }
+            None => commands.entity(*entity).despawn_recursive(),
+        }
+    }
+
+    for state in recorded {
+        let species = state.species as usize;
+        if species >= config.species.len() {
+            continue;
+        }
+
+        spawn_boid_exact(
+            &mut commands,
+            &mut config,
+            species,
+            Boid {
+                position: Vec2::from_array(state.position),
+                velocity: Vec2::from_array(state.velocity),
+                initial_color: LinearRgba::from_f32_array(state.initial_color).into(),
+            },
+        );
+    }
+
+    replay.shown = Some(index);
+}
+
+/// Shows the recorded brush while replaying.
+pub fn render_replay_input_gizmo(replay: Res<Replay>, mut gizmos: Gizmos) {
+    let Some(input) = replay.frame().and_then(|(_, frame)| frame.input.as_ref()) else {
+        return;
+    };
+
+    let color = match ToolMode::ALL.get(input.mode as usize) {
+        Some(ToolMode::Attract) => Color::srgb(0.2, 1.0, 0.4),
+        Some(ToolMode::Repel) => Color::srgb(1.0, 0.3, 0.3),
+        Some(ToolMode::Spawn) => Color::srgb(0.3, 0.6, 1.0),
+        _ => Color::srgb(1.0, 0.6, 0.1),
+    };
+    gizmos.circle_2d(Vec2::from_array(input.cursor), input.brush_radius, color);
+}
+
+pub struct TimelineState {
+    name: String,
+    status: Option<String>,
+}
+
+impl Default for TimelineState {
+    fn default() -> Self {
+        TimelineState {
+            name: "recording".to_string(),
+            status: None,
+        }
+    }
+}
+
+pub fn timeline_ui(
+    mut contexts: EguiContexts,
+    mut recorder: ResMut<Recorder>,
+    mut replay: ResMut<Replay>,
+    config: Query<&BoidConfiguration>,
+    mut state: Local<TimelineState>,
+) {
+    let config = config.single();
+    let path = PathBuf::from(RECORDING_DIR).join(format!("{}.brec", state.name));
+
+    egui::TopBottomPanel::bottom("timeline").show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut state.name);
+
+            match recorder.recording.take() {
+                Some(recording) => {
+                    ui.label(format!("recording, {} ticks", recording.frames.len()));
+                    let full = recording.is_full();
+                    if ui.button("stop").clicked() || full {
+                        state.status = Some(match recording.save(&path) {
+                            Ok(()) if full => format!("recording full, saved {}", path.display()),
+                            Ok(()) => format!("saved {}", path.display()),
+                            Err(error) => error.to_string(),
+                        });
+                        replay.start(recording);
+                    } else {
+                        recorder.recording = Some(recording);
+                    }
+                }
+                None => {
+                    if ui
+                        .add_enabled(replay.recording.is_none(), egui::Button::new("record"))
+                        .clicked()
+                    {
+                        recorder.recording = Some(Recording::new(config.tick_rate));
+                        state.status = None;
+                    }
+
+                    if ui.button("open").clicked() {
+                        state.status = Some(match Recording::load(&path) {
+                            Ok(recording) => {
+                                let status = format!("opened {}", path.display());
+                                replay.start(recording);
+                                status
+                            }
+                            Err(error) => error.to_string(),
+                        });
+                    }
+                }
+            }
+
+            if let Some(status) = &state.status {
+                ui.label(status);
+            }
+        });
+
+        let Some(frames) = replay.recording.as_ref().map(|r| r.frames.len()) else {
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            let label = if replay.playing { "pause" } else { "play" };
+            if ui.button(label).clicked() {
+                if !replay.playing && replay.playhead as usize + 1 >= frames {
+                    replay.playhead = 0.0;
+                }
+                replay.playing = !replay.playing;
+            }
+
+            ui.label("speed");
+            ui.add(egui::Slider::new(&mut replay.speed, 0.1..=8.0f32).logarithmic(true));
+
+            let last = frames.saturating_sub(1) as f32;
+            let mut playhead = replay.playhead.floor();
+            ui.spacing_mut().slider_width = (ui.available_width() - 200.0).max(100.0);
+            if ui
+                .add(egui::Slider::new(&mut playhead, 0.0..=last).step_by(1.0))
+                .changed()
+            {
+                replay.playhead = playhead;
+                replay.playing = false;
+            }
+
+            if ui.button("exit replay").clicked() {
+                replay.recording = None;
+            }
+        });
+    });
+}
+
+#[cfg(test)]
+mod test {
+
+    use bevy::prelude::*;
+
+    use crate::replay::{
+        RecordedBoid, RecordedFrame, Recorder, Recording, RecordingError, Replay,
+        MAX_RECORDING_FRAMES,
+    };
+    use crate::{test_app, Boid, BoidConfiguration, Species};
+
+    fn sorted(mut boids: Vec<RecordedBoid>) -> Vec<RecordedBoid> {
+        boids.sort_by(|a, b| a.position.partial_cmp(&b.position).unwrap());
+        boids
+    }
+
+    fn boids(app: &mut App) -> Vec<RecordedBoid> {
+        let boids = app
+            .world_mut()
+            .query::<(&Boid, &Species)>()
+            .iter(app.world())
+            .map(|(boid, species)| RecordedBoid {
+                species: species.0 as u16,
+                position: boid.position.to_array(),
+                velocity: boid.velocity.to_array(),
+                initial_color: boid.initial_color.to_linear().to_f32_array(),
+            })
+            .collect();
+        sorted(boids)
+    }
+
+    #[test]
+    fn records_and_replays() {
+        let mut app = test_app(30, None);
+        app.update();
+
+        app.world_mut().resource_mut::<Recorder>().recording = Some(Recording::new(60.0));
+        for _ in 0..20 {
+            app.update();
+        }
+        let recording = app
+            .world_mut()
+            .resource_mut::<Recorder>()
+            .recording
+            .take()
+            .unwrap();
+        assert!(recording.frames.len() > 10);
+        assert!(recording.frames[0].config.is_some());
+
+        assert!(matches!(
+            Recording::from_bytes(b"not a recording"),
+            Err(RecordingError::NotARecording)
+        ));
+        let mut old = recording.clone();
+        old.version = 1;
+        assert!(matches!(
+            Recording::from_bytes(&old.to_bytes().unwrap()),
+            Err(RecordingError::Version(1))
+        ));
+
+        let recording = Recording::from_bytes(&recording.to_bytes().unwrap()).unwrap();
+        let frame = sorted(recording.frames[5].boids.clone());
+
+        // erase a few so the replay has to spawn them back
+        let extra = app
+            .world_mut()
+            .query_filtered::<Entity, With<Boid>>()
+            .iter(app.world())
+            .take(5)
+            .collect::<Vec<_>>();
+        for entity in extra {
+            app.world_mut().despawn(entity);
+        }
+
+        let mut replay = Replay::default();
+        replay.start(recording);
+        replay.playhead = 5.0;
+        replay.playing = false;
+        app.insert_resource(replay);
+        for _ in 0..3 {
+            app.update();
+        }
+
+        assert_eq!(boids(&mut app), frame, "placed from the frame, unmoved");
+
+        app.world_mut().resource_mut::<Replay>().recording = None;
+        for _ in 0..5 {
+            app.update();
+        }
+        let count = app.world_mut().query::<&Boid>().iter(app.world()).count();
+        let config = app
+            .world_mut()
+            .query::<&BoidConfiguration>()
+            .single(app.world())
+            .clone();
+        assert_eq!(count, frame.len(), "carries on from the replayed flock");
+        assert_eq!(config.spawn_count() as usize, frame.len());
+    }
+
+    #[test]
+    fn records_the_config_when_it_changes() {
+        let mut app = test_app(10, None);
+        app.update();
+
+        let touch = |app: &mut App, turn_factor: Option<f32>| {
+            let mut config = app
+                .world_mut()
+                .query::<&mut BoidConfiguration>()
+                .single_mut(app.world_mut());
+            // what the ui does every frame, changed or not
+            let config = &mut *config;
+            if let Some(turn_factor) = turn_factor {
+                config.turn_factor = turn_factor;
+            }
+        };
+
+        app.world_mut().resource_mut::<Recorder>().recording = Some(Recording::new(60.0));
+        for _ in 0..10 {
+            touch(&mut app, None);
+            app.update();
+        }
+
+        // a predator kill lowers the count without touching the tuning
+        let world = app.world_mut();
+        world
+            .query::<&mut BoidConfiguration>()
+            .single_mut(world)
+            .species[0]
+            .spawn_count -= 1;
+        app.update();
+
+        touch(&mut app, Some(2.0));
+        for _ in 0..10 {
+            app.update();
+        }
+
+        let recording = app
+            .world_mut()
+            .resource_mut::<Recorder>()
+            .recording
+            .take()
+            .unwrap();
+        let configs = recording
+            .frames
+            .iter()
+            .filter_map(|frame| frame.config.as_ref())
+            .collect::<Vec<_>>();
+        assert!(recording.frames.len() > 10);
+        assert_eq!(
+            configs.len(),
+            2,
+            "the first frame and the turn factor change"
+        );
+        assert_eq!(configs[1].turn_factor, 2.0);
+    }
+
+    #[test]
+    fn stops_recording_when_full() {
+        let mut app = test_app(10, None);
+        app.update();
+
+        let mut recording = Recording::new(60.0);
+        recording.frames = vec![RecordedFrame::default(); MAX_RECORDING_FRAMES - 1];
+        app.world_mut().resource_mut::<Recorder>().recording = Some(recording);
+        for _ in 0..5 {
+            app.update();
+        }
+
+        let recording = app
+            .world_mut()
+            .resource_mut::<Recorder>()
+            .recording
+            .take()
+            .unwrap();
+        assert!(recording.is_full());
+        assert_eq!(recording.frames.len(), MAX_RECORDING_FRAMES);
+    }
+}
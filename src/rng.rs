@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::boid::Boid;
+use crate::config::BoidConfiguration;
+
+/// All randomness in the simulation is drawn from here so a seed reproduces a run.
+///
+/// Color sampling uses its own stream so whether or not the render systems run doesn't change
+/// the flock, and so do the editors, so placing things from the UI doesn't either.
+#[derive(Resource)]
+pub struct BoidRng {
+    pub simulation: StdRng,
+    pub cosmetic: StdRng,
+    pub editor: StdRng,
+}
+
+impl BoidRng {
+    pub fn new(seed: u64) -> Self {
+        BoidRng {
+            simulation: StdRng::seed_from_u64(seed),
+            cosmetic: StdRng::seed_from_u64(seed.wrapping_add(1)),
+            editor: StdRng::seed_from_u64(seed.wrapping_add(2)),
+        }
+    }
+}
+
+/// Despawns the flock and reseeds the rng from `BoidConfiguration::seed`.
+#[derive(Event, Default)]
+pub struct RestartSimulation;
+
+pub fn boid_restart(
+    mut commands: Commands,
+    mut restarts: EventReader<RestartSimulation>,
+    mut rng: ResMut<BoidRng>,
+    mut config: Query<&mut BoidConfiguration>,
+    boids: Query<Entity, With<Boid>>,
+) {
+    if restarts.is_empty() {
+        return;
+    }
+    restarts.clear();
+
+    let mut config = config.single_mut();
+
+    for entity in boids.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    config.total_boids = 0;
+    *rng = BoidRng::new(config.seed);
+}
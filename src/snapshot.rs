@@ -0,0 +1,395 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_egui::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::boid::{spawn_boid_exact, Boid, Species};
+use crate::config::BoidConfiguration;
+use crate::rng::BoidRng;
+
+/// Bumped whenever `Snapshot` changes shape. Older files are refused rather than misread.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Starts every binary snapshot, followed by the bincode encoded `Snapshot`.
+pub const BINARY_MAGIC: &[u8; 4] = b"BOID";
+
+/// Where snapshots are saved, relative to the working directory.
+pub const SNAPSHOT_DIR: &str = "snapshots";
+
+/// The whole flock at one tick.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    /// The configuration, with each species' `spawn_count` set to the boids it has in the
+    /// snapshot so `boid_ensure_count` leaves the restored flock alone. It holds the seed.
+    pub config: BoidConfiguration,
+    /// `BoidConfiguration::boid_bounds`, which the configuration leaves out when serialized.
+    pub bounds: [f32; 4],
+    pub boids: Vec<BoidSnapshot>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BoidSnapshot {
+    pub species: usize,
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    /// `Boid::initial_color` as linear RGBA.
+    pub initial_color: [f32; 4],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    Json,
+    Binary,
+}
+
+impl SnapshotFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            SnapshotFormat::Json => "json",
+            SnapshotFormat::Binary => "boids",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Binary(bincode::Error),
+    Version(u32),
+    /// The snapshot parsed but can't be restored, such as bounds that aren't a rectangle.
+    Format(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(error) => write!(f, "{}", error),
+            SnapshotError::Json(error) => write!(f, "{}", error),
+            SnapshotError::Binary(error) => write!(f, "{}", error),
+            SnapshotError::Version(version) => write!(
+                f,
+                "snapshot version {} is not supported, expected {}",
+                version, SNAPSHOT_VERSION
+            ),
+            SnapshotError::Format(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl Snapshot {
+    pub fn capture<'a>(
+        config: &BoidConfiguration,
+        boids: impl Iterator<Item = (&'a Boid, &'a Species)>,
+    ) -> Self {
+        let mut config = config.clone();
+        for species in config.species.iter_mut() {
+            species.spawn_count = 0;
+        }
+
+        let boids = boids
+            .map(|(boid, species)| {
+                if let Some(species) = config.species.get_mut(species.0) {
+                    species.spawn_count += 1;
+                }
+
+                BoidSnapshot {
+                    species: species.0,
+                    position: boid.position.to_array(),
+                    velocity: boid.velocity.to_array(),
+                    initial_color: boid.initial_color.to_linear().to_f32_array(),
+                }
+            })
+            .collect();
+
+        let bounds = config.boid_bounds;
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            config,
+            bounds: [bounds.min.x, bounds.min.y, bounds.max.x, bounds.max.y],
+            boids,
+        }
+    }
+
+    pub fn to_bytes(&self, format: SnapshotFormat) -> Result<Vec<u8>, SnapshotError> {
+        match format {
+            SnapshotFormat::Json => serde_json::to_vec_pretty(self).map_err(SnapshotError::Json),
+            SnapshotFormat::Binary => {
+                let mut bytes = BINARY_MAGIC.to_vec();
+                bincode::serialize_into(&mut bytes, self).map_err(SnapshotError::Binary)?;
+                Ok(bytes)
+            }
+        }
+    }
+
+    /// Reads either format, telling them apart by the binary header.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let snapshot: Snapshot = match bytes.strip_prefix(BINARY_MAGIC) {
+            Some(body) => {
+                // the version leads the encoding, so check it before decoding the rest
+                let version = bincode::deserialize::<u32>(body).map_err(SnapshotError::Binary)?;
+                if version != SNAPSHOT_VERSION {
+                    return Err(SnapshotError::Version(version));
+                }
+                bincode::deserialize(body).map_err(SnapshotError::Binary)?
+            }
+            None => {
+                #[derive(Deserialize)]
+                struct Versioned {
+                    version: u32,
+                }
+
+                let Versioned { version } =
+                    serde_json::from_slice(bytes).map_err(SnapshotError::Json)?;
+                if version != SNAPSHOT_VERSION {
+                    return Err(SnapshotError::Version(version));
+                }
+                serde_json::from_slice(bytes).map_err(SnapshotError::Json)?
+            }
+        };
+
+        // the indexes are built over these bounds, so an inverted or infinite box can't be used
+        let [min_x, min_y, max_x, max_y] = snapshot.bounds;
+        if !(snapshot.bounds.iter().all(|value| value.is_finite())
+            && min_x < max_x
+            && min_y < max_y)
+        {
+            return Err(SnapshotError::Format(format!(
+                "snapshot bounds {:?} are not a rectangle",
+                snapshot.bounds
+            )));
+        }
+
+        Ok(snapshot)
+    }
+
+    pub fn save(&self, path: &Path, format: SnapshotFormat) -> Result<(), SnapshotError> {
+        let bytes = self.to_bytes(format)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(SnapshotError::Io)?;
+        }
+        std::fs::write(path, bytes).map_err(SnapshotError::Io)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SnapshotError> {
+        Snapshot::from_bytes(&std::fs::read(path).map_err(SnapshotError::Io)?)
+    }
+}
+
+/// Replaces the flock with a snapshot. The rng is reseeded from the snapshot's seed.
+#[derive(Event)]
+pub struct RestoreSnapshot(pub Snapshot);
+
+pub fn snapshot_restore(
+    mut commands: Commands,
+    mut restores: EventReader<RestoreSnapshot>,
+    mut rng: ResMut<BoidRng>,
+    mut config: Query<&mut BoidConfiguration>,
+    boids: Query<Entity, With<Boid>>,
+) {
+    let Some(RestoreSnapshot(snapshot)) = restores.read().last() else {
+        return;
+    };
+
+    let mut config = config.single_mut();
+
+    for entity in boids.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let [min_x, min_y, max_x, max_y] = snapshot.bounds;
+    *config = BoidConfiguration {
+        total_boids: 0,
+        spawn_range: config.spawn_range,
+        boid_bounds: Rect::new(min_x, min_y, max_x, max_y),
+        ..snapshot.config.clone()
+    };
+    config.fill_interactions();
+    config.enforce_limits();
+    *rng = BoidRng::new(config.seed);
+
+    for boid in snapshot.boids.iter() {
+        if boid.species >= config.species.len() {
+            continue;
+        }
+
+        spawn_boid_exact(
+            &mut commands,
+            &mut config,
+            boid.species,
+            Boid {
+                position: Vec2::from_array(boid.position),
+                velocity: Vec2::from_array(boid.velocity),
+                initial_color: LinearRgba::from_f32_array(boid.initial_color).into(),
+            },
+        );
+    }
+}
+
+pub struct SnapshotState {
+    name: String,
+    format: SnapshotFormat,
+    status: Option<String>,
+}
+
+impl Default for SnapshotState {
+    fn default() -> Self {
+        SnapshotState {
+            name: "snapshot".to_string(),
+            format: SnapshotFormat::Binary,
+            status: None,
+        }
+    }
+}
+
+/// The snapshot section of `boids_ui`.
+#[derive(SystemParam)]
+pub struct SnapshotEditor<'w, 's> {
+    boids: Query<'w, 's, (&'static Boid, &'static Species)>,
+    restores: EventWriter<'w, RestoreSnapshot>,
+    state: Local<'s, SnapshotState>,
+}
+
+impl SnapshotEditor<'_, '_> {
+    pub fn ui(&mut self, ui: &mut egui::Ui, config: &BoidConfiguration) {
+        let state = &mut *self.state;
+        let path = |state: &SnapshotState| -> PathBuf {
+            PathBuf::from(SNAPSHOT_DIR).join(format!("{}.{}", state.name, state.format.extension()))
+        };
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut state.name);
+            ui.radio_value(&mut state.format, SnapshotFormat::Binary, "binary");
+            ui.radio_value(&mut state.format, SnapshotFormat::Json, "json");
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("save").clicked() {
+                let path = path(state);
+                let snapshot = Snapshot::capture(config, self.boids.iter());
+                state.status = Some(match snapshot.save(&path, state.format) {
+                    Ok(()) => format!("saved {} boids to {}", snapshot.boids.len(), path.display()),
+                    Err(error) => error.to_string(),
+                });
+            }
+
+            if ui.button("restore").clicked() {
+                let path = path(state);
+                state.status = Some(match Snapshot::load(&path) {
+                    Ok(snapshot) => {
+                        let status = format!("restored {}", path.display());
+                        self.restores.send(RestoreSnapshot(snapshot));
+                        status
+                    }
+                    Err(error) => error.to_string(),
+                });
+            }
+        });
+
+        if let Some(status) = &state.status {
+            ui.label(status);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::*;
+
+    use crate::snapshot::{
+        snapshot_restore, RestoreSnapshot, Snapshot, SnapshotError, SnapshotFormat,
+        SNAPSHOT_VERSION,
+    };
+    use crate::{test_app, Boid, BoidConfiguration, Species};
+
+    fn app() -> App {
+        let mut app = test_app(40, Some(3));
+        app.update();
+        app
+    }
+
+    fn capture(app: &mut App) -> Snapshot {
+        let config = app
+            .world_mut()
+            .query::<&BoidConfiguration>()
+            .single(app.world())
+            .clone();
+        let mut boids = app.world_mut().query::<(&Boid, &Species)>();
+        Snapshot::capture(&config, boids.iter(app.world()))
+    }
+
+    fn sorted_boids(snapshot: &Snapshot) -> Vec<([f32; 2], [f32; 2])> {
+        let mut boids = snapshot
+            .boids
+            .iter()
+            .map(|boid| (boid.position, boid.velocity))
+            .collect::<Vec<_>>();
+        boids.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        boids
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut app = app();
+        let snapshot = capture(&mut app);
+
+        for format in [SnapshotFormat::Json, SnapshotFormat::Binary] {
+            let bytes = snapshot.to_bytes(format).unwrap();
+            let loaded = Snapshot::from_bytes(&bytes).unwrap();
+            assert_eq!(loaded.boids, snapshot.boids, "{:?}", format);
+            assert_eq!(loaded.bounds, snapshot.bounds, "{:?}", format);
+            assert_eq!(loaded.config.seed, snapshot.config.seed, "{:?}", format);
+        }
+
+        let inverted = Snapshot {
+            bounds: [100.0, -100.0, -100.0, 100.0],
+            ..snapshot.clone()
+        };
+        let old = Snapshot {
+            version: SNAPSHOT_VERSION + 1,
+            ..snapshot
+        };
+        for format in [SnapshotFormat::Json, SnapshotFormat::Binary] {
+            assert!(matches!(
+                Snapshot::from_bytes(&old.to_bytes(format).unwrap()),
+                Err(SnapshotError::Version(_))
+            ));
+            assert!(matches!(
+                Snapshot::from_bytes(&inverted.to_bytes(format).unwrap()),
+                Err(SnapshotError::Format(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn restores_exactly() {
+        let mut app = app();
+        for _ in 0..10 {
+            app.update();
+        }
+        let saved = capture(&mut app);
+
+        for _ in 0..10 {
+            app.update();
+        }
+        assert_ne!(sorted_boids(&capture(&mut app)), sorted_boids(&saved));
+
+        app.world_mut().send_event(RestoreSnapshot(saved.clone()));
+        app.world_mut().run_system_once(snapshot_restore).unwrap();
+        assert_eq!(sorted_boids(&capture(&mut app)), sorted_boids(&saved));
+
+        for _ in 0..10 {
+            app.update();
+        }
+        let count = app.world_mut().query::<&Boid>().iter(app.world()).count();
+        assert_eq!(count, saved.boids.len(), "ensure_count keeps the flock");
+    }
+}
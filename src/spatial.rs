@@ -0,0 +1,289 @@
+use bevy::math::{Rect, Vec2};
+
+/// Neighbour lookup used by the flocking rules.
+///
+/// Implemented by `Quadtree`, `SpatialHash` and `UniformGrid`; `SpatialState` picks which one
+/// backs the simulation.
+pub trait SpatialIndex<T: Clone> {
+    /// Replaces the contents of the index with `points`.
+    fn build(&mut self, points: impl IntoIterator<Item = (Vec2, T)>);
+
+    /// Number of points stored. Points outside the indexed area are dropped by `build`.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Calls `visit` for every point within `radius` of `center`. Returns how many points the
+    /// index looked at to find them, in range or not, which is what a query costs.
+    fn query_radius(&self, center: Vec2, radius: f32, visit: impl FnMut(Vec2, &T)) -> usize;
+
+    /// The `k` points closest to `center`, nearest first.
+    fn k_nearest(&self, center: Vec2, k: usize) -> Vec<(Vec2, T)> {
+        let mut found = vec![];
+        if k == 0 {
+            return found;
+        }
+
+        // grow the search radius until it holds k points; anything outside it is further away
+        // than everything inside
+        let mut radius = 16.0f32;
+        loop {
+            found.clear();
+            self.query_radius(center, radius, |point, value| {
+                found.push((point, value.clone()))
+            });
+
+            if found.len() >= k.min(self.len()) || !radius.is_finite() {
+                break;
+            }
+
+            radius *= 2.0;
+        }
+
+        found.sort_by(|(a, _), (b, _)| {
+            a.distance_squared(center)
+                .total_cmp(&b.distance_squared(center))
+        });
+        found.truncate(k);
+        found
+    }
+
+    /// `query_radius` on a torus the size of `bounds`. Points across a seam are visited at their
+    /// image next to `center`, so offsets from `center` come out right.
+    ///
+    /// The radius is capped at half the smaller side of `bounds` so no point is seen twice.
+    fn query_radius_wrapped(
+        &self,
+        center: Vec2,
+        radius: f32,
+        bounds: Rect,
+        mut visit: impl FnMut(Vec2, &T),
+    ) -> usize {
+        let size = bounds.size();
+        let radius = radius.min(size.min_element() / 2.0);
+        let mut examined = 0;
+
+        for y in [-1.0, 0.0, 1.0] {
+            for x in [-1.0, 0.0, 1.0] {
+                let shift = Vec2::new(x, y) * size;
+                let ghost = center + shift;
+
+                // only look through the seams the circle actually crosses
+                let outside = (bounds.min - ghost).max(ghost - bounds.max).max(Vec2::ZERO);
+                if outside.length() > radius {
+                    continue;
+                }
+
+                examined +=
+                    self.query_radius(ghost, radius, |point, value| visit(point - shift, value));
+            }
+        }
+        examined
+    }
+
+    /// `k_nearest` on a torus the size of `bounds`, with positions as in `query_radius_wrapped`.
+    fn k_nearest_wrapped(&self, center: Vec2, k: usize, bounds: Rect) -> Vec<(Vec2, T)> {
+        let mut found = vec![];
+        if k == 0 {
+            return found;
+        }
+
+        let max_radius = bounds.size().min_element() / 2.0;
+        let mut radius = 16.0f32.min(max_radius);
+        loop {
+            found.clear();
+            self.query_radius_wrapped(center, radius, bounds, |point, value| {
+                found.push((point, value.clone()))
+            });
+
+            if found.len() >= k.min(self.len()) || radius >= max_radius {
+                break;
+            }
+
+            radius = (radius * 2.0).min(max_radius);
+        }
+
+        found.sort_by(|(a, _), (b, _)| {
+            a.distance_squared(center)
+                .total_cmp(&b.distance_squared(center))
+        });
+        found.truncate(k);
+        found
+    }
+
+    /// `query_radius_wrapped` when `wrap` is set, otherwise `query_radius`.
+    fn query_radius_with_wrap(
+        &self,
+        center: Vec2,
+        radius: f32,
+        wrap: Option<Rect>,
+        visit: impl FnMut(Vec2, &T),
+    ) -> usize {
+        match wrap {
+            Some(bounds) => self.query_radius_wrapped(center, radius, bounds, visit),
+            None => self.query_radius(center, radius, visit),
+        }
+    }
+
+    /// `k_nearest_wrapped` when `wrap` is set, otherwise `k_nearest`.
+    fn k_nearest_with_wrap(&self, center: Vec2, k: usize, wrap: Option<Rect>) -> Vec<(Vec2, T)> {
+        match wrap {
+            Some(bounds) => self.k_nearest_wrapped(center, k, bounds),
+            None => self.k_nearest(center, k),
+        }
+    }
+
+    /// The `k` points closest to `center` that `accept` lets through, nearest first, so points
+    /// that are turned away don't use up any of the `k`. `found` is cleared first; pass the same
+    /// buffer each time to avoid allocating. Wraps like `k_nearest_with_wrap`. Returns how many
+    /// points were looked at, as `query_radius` does.
+    fn k_nearest_filtered(
+        &self,
+        center: Vec2,
+        k: usize,
+        wrap: Option<Rect>,
+        accept: impl FnMut(Vec2, &T) -> bool,
+        found: &mut Vec<(Vec2, T)>,
+    ) -> usize {
+        k_nearest_by_radius(self, center, k, wrap, accept, found)
+    }
+}
+
+/// `k_nearest_filtered` by querying ever larger radii until enough points are accepted or there
+/// is nowhere left to look.
+pub fn k_nearest_by_radius<T: Clone, I: SpatialIndex<T> + ?Sized>(
+    index: &I,
+    center: Vec2,
+    k: usize,
+    wrap: Option<Rect>,
+    mut accept: impl FnMut(Vec2, &T) -> bool,
+    found: &mut Vec<(Vec2, T)>,
+) -> usize {
+    found.clear();
+    if k == 0 || index.is_empty() {
+        return 0;
+    }
+
+    let mut examined = 0;
+
+    let max_radius = wrap.map_or(f32::INFINITY, |bounds| bounds.size().min_element() / 2.0);
+    let mut radius = 16.0f32.min(max_radius);
+    loop {
+        found.clear();
+        let mut within = 0;
+        examined += index.query_radius_with_wrap(center, radius, wrap, |point, value| {
+            within += 1;
+            if accept(point, value) {
+                found.push((point, value.clone()));
+            }
+        });
+
+        // once every point is in range a bigger radius won't turn up more
+        if found.len() >= k || within >= index.len() || radius >= max_radius {
+            break;
+        }
+
+        radius = (radius * 2.0).min(max_radius);
+    }
+
+    found.sort_by(|(a, _), (b, _)| {
+        a.distance_squared(center)
+            .total_cmp(&b.distance_squared(center))
+    });
+    found.truncate(k);
+    examined
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::{Rect, Vec2};
+
+    use crate::quadtree::Quadtree;
+    use crate::spatial::SpatialIndex;
+    use crate::spatial_hash::SpatialHash;
+    use crate::uniform_grid::UniformGrid;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Point(u32);
+
+    impl From<&Point> for u32 {
+        fn from(point: &Point) -> u32 {
+            point.0
+        }
+    }
+
+    fn check_wrapped(index: &mut impl SpatialIndex<Point>) {
+        let bounds = Rect::new(-100.0, -100.0, 100.0, 100.0);
+        index.build([
+            (Vec2::new(95.0, 0.0), Point(0)),
+            (Vec2::new(-95.0, 0.0), Point(1)),
+            (Vec2::new(-95.0, -95.0), Point(2)),
+            (Vec2::new(0.0, 0.0), Point(3)),
+        ]);
+
+        let mut seen = vec![];
+        index.query_radius_wrapped(Vec2::new(95.0, 95.0), 20.0, bounds, |point, value| {
+            seen.push((point, value.0))
+        });
+        seen.sort_by_key(|(_, id)| *id);
+        assert_eq!(
+            seen,
+            vec![(Vec2::new(105.0, 105.0), 2)],
+            "the far corner is next door"
+        );
+
+        let nearest = index.k_nearest_wrapped(Vec2::new(95.0, 0.0), 2, bounds);
+        assert_eq!(nearest[0], (Vec2::new(95.0, 0.0), Point(0)));
+        assert_eq!(
+            nearest[1],
+            (Vec2::new(105.0, 0.0), Point(1)),
+            "across the seam"
+        );
+    }
+
+    #[test]
+    fn wrapped_queries() {
+        let mut quadtree: Quadtree<u32, Point> =
+            Quadtree::new(Rect::new(-200.0, -200.0, 200.0, 200.0), 2);
+        check_wrapped(&mut quadtree);
+
+        let mut hash = SpatialHash::new(Rect::new(-200.0, -200.0, 200.0, 200.0), 20.0);
+        check_wrapped(&mut hash);
+    }
+
+    fn check_filtered(index: &mut impl SpatialIndex<Point>) {
+        index.build((0..40).map(|i| (Vec2::new(i as f32 * 3.0, 0.0), Point(i))));
+
+        let mut found = vec![];
+        for wrap in [None, Some(Rect::new(-150.0, -150.0, 150.0, 150.0))] {
+            // the closest points are all odd, so they'd take every place without the filter
+            index.k_nearest_filtered(
+                Vec2::ZERO,
+                4,
+                wrap,
+                |_, point| point.0 % 2 == 0 && point.0 > 0,
+                &mut found,
+            );
+            let ids = found.iter().map(|(_, point)| point.0).collect::<Vec<_>>();
+            assert_eq!(ids, vec![2, 4, 6, 8], "wrap {:?}", wrap);
+        }
+
+        index.k_nearest_filtered(Vec2::ZERO, 4, None, |_, point| point.0 == 7, &mut found);
+        assert_eq!(found.len(), 1, "fewer than k when that's all there is");
+    }
+
+    #[test]
+    fn filtered_k_nearest() {
+        let mut quadtree: Quadtree<u32, Point> =
+            Quadtree::new(Rect::new(-200.0, -200.0, 200.0, 200.0), 2);
+        check_filtered(&mut quadtree);
+
+        let mut hash = SpatialHash::new(Rect::new(-200.0, -200.0, 200.0, 200.0), 20.0);
+        check_filtered(&mut hash);
+
+        let mut grid = UniformGrid::new(Rect::new(-200.0, -200.0, 200.0, 200.0), 20.0);
+        check_filtered(&mut grid);
+    }
+}
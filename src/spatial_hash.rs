@@ -0,0 +1,180 @@
+use bevy::math::{Rect, UVec2, Vec2};
+use bevy::prelude::Resource;
+use bevy::utils::hashbrown::HashMap;
+
+use crate::spatial::SpatialIndex;
+
+/// Buckets points into square cells of `cell_size` inside `bounds`. Cells are hashed into a
+/// fixed number of buckets, so distant cells can share one.
+#[derive(Resource, Debug)]
+pub struct SpatialHash<T> {
+    bounds: Rect,
+    cell_size: f32,
+    cells: UVec2,
+    buckets: HashMap<u32, Vec<(Vec2, T)>>,
+    count: usize,
+}
+
+impl<T> SpatialHash<T> {
+    pub fn new(bounds: Rect, cell_size: f32) -> Self {
+        let mut hash = SpatialHash {
+            bounds,
+            cell_size,
+            cells: UVec2::ZERO,
+            buckets: HashMap::new(),
+            count: 0,
+        };
+        hash.resize(bounds, cell_size);
+        hash
+    }
+
+    /// Changes the grid layout. Takes effect on the next `build`.
+    pub fn resize(&mut self, bounds: Rect, cell_size: f32) {
+        self.bounds = bounds;
+        self.cell_size = cell_size;
+        // at least one cell, so `key` never hashes modulo zero
+        self.cells = (bounds.size() / cell_size).ceil().max(Vec2::ONE).as_uvec2();
+    }
+
+    pub fn bounds(&self) -> Rect {
+        self.bounds
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    fn key(&self, cell: UVec2) -> u32 {
+        hash_coords(cell.x, cell.y, self.cells.x.saturating_mul(self.cells.y))
+    }
+}
+
+pub fn hash_coords(x: u32, y: u32, num_cells: u32) -> u32 {
+    let h = (x as u64 * 92837111) ^ (y as u64 * 689287499);
+    (h % num_cells as u64) as u32
+}
+
+pub fn find_cell_position(position: Vec2, bounds: Rect, cell_size: f32) -> Option<UVec2> {
+    let from_bounds = position - bounds.min;
+    if from_bounds.x < 0.0
+        || from_bounds.y < 0.0
+        || from_bounds.x >= bounds.size().x
+        || from_bounds.y >= bounds.size().y
+    {
+        // //skip
+        return None;
+    }
+
+    let cell_x = (from_bounds.x / cell_size).floor();
+    let cell_y = (from_bounds.y / cell_size).floor();
+
+    Some(UVec2::new(cell_x as u32, cell_y as u32))
+}
+
+impl<T: Clone> SpatialIndex<T> for SpatialHash<T> {
+    fn build(&mut self, points: impl IntoIterator<Item = (Vec2, T)>) {
+        // keep the bucket allocations around between frames
+        for bucket in self.buckets.values_mut() {
+            bucket.clear();
+        }
+        self.count = 0;
+
+        for (point, value) in points {
+            if let Some(cell) = find_cell_position(point, self.bounds, self.cell_size) {
+                let key = self.key(cell);
+                self.buckets.entry(key).or_default().push((point, value));
+                self.count += 1;
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.count
+    }
+
+    fn query_radius(&self, center: Vec2, radius: f32, mut visit: impl FnMut(Vec2, &T)) -> usize {
+        let max_cell = (self.cells - 1).as_vec2();
+        let min = ((center - radius - self.bounds.min) / self.cell_size)
+            .floor()
+            .clamp(Vec2::ZERO, max_cell)
+            .as_uvec2();
+        let max = ((center + radius - self.bounds.min) / self.cell_size)
+            .floor()
+            .clamp(Vec2::ZERO, max_cell)
+            .as_uvec2();
+
+        let radius_squared = radius * radius;
+        let mut examined = 0;
+
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let cell = UVec2::new(x, y);
+                let Some(bucket) = self.buckets.get(&self.key(cell)) else {
+                    continue;
+                };
+                examined += bucket.len();
+
+                for (point, value) in bucket {
+                    // buckets are shared between colliding cells, only take this cell's points
+                    if find_cell_position(*point, self.bounds, self.cell_size) != Some(cell) {
+                        continue;
+                    }
+
+                    if point.distance_squared(center) <= radius_squared {
+                        visit(*point, value);
+                    }
+                }
+            }
+        }
+        examined
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::{Rect, Vec2};
+
+    use crate::spatial::SpatialIndex;
+    use crate::spatial_hash::SpatialHash;
+
+    #[test]
+    fn query_radius_visits_each_point_once() {
+        let mut hash = SpatialHash::new(Rect::new(-100.0, -100.0, 100.0, 100.0), 10.0);
+        hash.build((0..100).map(|i| (Vec2::new(i as f32 - 50.0, 0.0), i)));
+
+        let mut found = vec![];
+        hash.query_radius(Vec2::ZERO, 10.0, |_, value| found.push(*value));
+        found.sort();
+
+        assert_eq!(found, (40..=60).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn k_nearest() {
+        let mut hash = SpatialHash::new(Rect::new(-100.0, -100.0, 100.0, 100.0), 10.0);
+        hash.build((0..10).map(|i| (Vec2::new(i as f32 * 5.0, 0.0), i)));
+
+        let nearest: Vec<_> = hash
+            .k_nearest(Vec2::new(21.0, 0.0), 3)
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect();
+
+        assert_eq!(nearest, vec![4, 5, 3]);
+    }
+
+    #[test]
+    fn bounds_smaller_than_a_cell() {
+        let mut hash = SpatialHash::new(Rect::new(-10.0, -10.0, 10.0, 10.0), 100.0);
+        hash.build([(Vec2::new(1.0, 1.0), 1), (Vec2::new(-5.0, 5.0), 2)]);
+
+        let mut found = vec![];
+        hash.query_radius(Vec2::ZERO, 20.0, |_, value| found.push(*value));
+        found.sort();
+
+        assert_eq!(hash.len(), 2);
+        assert_eq!(found, vec![1, 2]);
+        // both share the one cell, so both are looked at even when neither is in range
+        assert_eq!(hash.query_radius(Vec2::ZERO, 1.0, |_, _| unreachable!()), 2);
+    }
+}
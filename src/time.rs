@@ -0,0 +1,47 @@
+use bevy::app::FixedMain;
+use bevy::prelude::*;
+
+use crate::config::BoidConfiguration;
+
+/// Runs a single simulation tick while virtual time is paused.
+#[derive(Event, Default)]
+pub struct StepSimulation;
+
+pub fn sync_tick_rate(
+    config: Query<&BoidConfiguration, Changed<BoidConfiguration>>,
+    mut time: ResMut<Time<Fixed>>,
+) {
+    if let Ok(config) = config.get_single() {
+        // `from_hz` panics on these; `enforce_limits` should have caught them already
+        if !(config.tick_rate.is_finite() && config.tick_rate > 0.0) {
+            return;
+        }
+
+        let timestep = Time::<Fixed>::from_hz(config.tick_rate).timestep();
+        if time.timestep() != timestep {
+            time.set_timestep(timestep);
+        }
+    }
+}
+
+// Mirrors what `RunFixedMainLoop` does for one tick, so the simulation systems see the fixed
+// timestep as `Time` even though virtual time is not advancing.
+pub fn boid_single_step(world: &mut World) {
+    let steps = world
+        .resource_mut::<Events<StepSimulation>>()
+        .drain()
+        .count();
+
+    if steps == 0 || !world.resource::<Time<Virtual>>().is_paused() {
+        return;
+    }
+
+    for _ in 0..steps {
+        let timestep = world.resource::<Time<Fixed>>().timestep();
+        world.resource_mut::<Time<Fixed>>().advance_by(timestep);
+        *world.resource_mut::<Time>() = world.resource::<Time<Fixed>>().as_generic();
+        world.run_schedule(FixedMain);
+    }
+
+    *world.resource_mut::<Time>() = world.resource::<Time<Virtual>>().as_generic();
+}
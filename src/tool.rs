@@ -0,0 +1,244 @@
+use std::f32::consts::{FRAC_1_SQRT_2, TAU};
+
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::boid::{spawn_boid_in, Boid, Species};
+use crate::config::BoidConfiguration;
+use crate::highlight::cursor_world_position;
+use crate::rng::BoidRng;
+
+/// What holding the mouse button in the world does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ToolMode {
+    /// Click to highlight the nearest boid.
+    #[default]
+    Select,
+    /// Pull boids within the brush towards the cursor.
+    Attract,
+    /// Push boids within the brush away from the cursor.
+    Repel,
+    /// Add a boid under the brush every tick.
+    Spawn,
+    /// Remove every boid under the brush.
+    Erase,
+    /// Click to add and remove waypoints.
+    Path,
+}
+
+impl ToolMode {
+    pub const ALL: [ToolMode; 6] = [
+        ToolMode::Select,
+        ToolMode::Attract,
+        ToolMode::Repel,
+        ToolMode::Spawn,
+        ToolMode::Erase,
+        ToolMode::Path,
+    ];
+}
+
+/// The mouse tool and where it is. The cursor is sampled every frame and used by the next
+/// simulation tick.
+#[derive(Resource, Debug)]
+pub struct MouseTool {
+    pub mode: ToolMode,
+    pub brush_radius: f32,
+    /// Velocity change per tick at the centre of the brush for `Attract` and `Repel`.
+    pub strength: f32,
+    /// Which species `Spawn` adds.
+    pub species: usize,
+    /// The cursor in world space, `None` when it is off the window or over the UI.
+    pub cursor: Option<Vec2>,
+    /// Whether the left button is held.
+    pub active: bool,
+}
+
+impl Default for MouseTool {
+    fn default() -> Self {
+        MouseTool {
+            mode: ToolMode::Select,
+            brush_radius: 80.0,
+            strength: 5.0,
+            species: 0,
+            cursor: None,
+            active: false,
+        }
+    }
+}
+
+pub fn tool_track_cursor(
+    mut tool: ResMut<MouseTool>,
+    mut contexts: EguiContexts,
+    mouse: Res<ButtonInput<MouseButton>>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+) {
+    let over_ui = contexts.ctx_mut().wants_pointer_input();
+
+    let (camera, camera_transform) = camera.single();
+    let cursor = q_windows
+        .get_single()
+        .ok()
+        .and_then(|window| cursor_world_position(window, camera, camera_transform))
+        .filter(|_| !over_ui);
+
+    tool.cursor = cursor;
+    tool.active = cursor.is_some() && mouse.pressed(MouseButton::Left);
+}
+
+/// Attract and repel push boids inside the brush, harder towards the centre.
+pub fn tool_apply_force(tool: Res<MouseTool>, mut boids: Query<&mut Boid>) {
+    let direction = match tool.mode {
+        ToolMode::Attract => 1.0,
+        ToolMode::Repel => -1.0,
+        _ => return,
+    };
+
+    let Some(cursor) = tool.cursor.filter(|_| tool.active) else {
+        return;
+    };
+
+    for mut boid in boids.iter_mut() {
+        let offset = cursor - boid.position;
+        let distance = offset.length();
+        if distance > tool.brush_radius {
+            continue;
+        }
+
+        let falloff = 1.0 - distance / tool.brush_radius.max(f32::EPSILON);
+        boid.velocity += offset.normalize_or_zero() * direction * tool.strength * falloff;
+    }
+}
+
+/// Spawn and erase change the species' `spawn_count` along with the boids so
+/// `boid_ensure_count` keeps them.
+pub fn tool_spawn_erase(
+    mut commands: Commands,
+    tool: Res<MouseTool>,
+    mut rng: ResMut<BoidRng>,
+    mut config: Query<&mut BoidConfiguration>,
+    boids: Query<(Entity, &Boid, &Species)>,
+) {
+    let Some(cursor) = tool.cursor.filter(|_| tool.active) else {
+        return;
+    };
+
+    let mut config = config.single_mut();
+
+    match tool.mode {
+        ToolMode::Spawn => {
+            let species = tool.species;
+            if species >= config.species.len() {
+                return;
+            }
+
+            // keep inside the circle the brush shows
+            let area =
+                Rect::from_center_half_size(cursor, Vec2::splat(tool.brush_radius * FRAC_1_SQRT_2));
+            spawn_boid_in(&mut commands, &mut config, species, area, &mut rng.editor);
+            config.species[species].spawn_count += 1;
+        }
+        ToolMode::Erase => {
+            for (entity, boid, species) in boids.iter() {
+                if boid.position.distance(cursor) > tool.brush_radius {
+                    continue;
+                }
+
+                commands.entity(entity).despawn_recursive();
+                if let Some(species) = config.species.get_mut(species.0) {
+                    species.spawn_count = species.spawn_count.saturating_sub(1);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+pub fn render_tool_gizmo(tool: Res<MouseTool>, mut gizmos: Gizmos) {
+    let Some(cursor) = tool.cursor else {
+        return;
+    };
+
+    let radius = tool.brush_radius;
+    let alpha = if tool.active { 0.9 } else { 0.4 };
+
+    match tool.mode {
+        ToolMode::Select | ToolMode::Path => {}
+        ToolMode::Attract | ToolMode::Repel => {
+            let color = if tool.mode == ToolMode::Attract {
+                Color::srgba(0.2, 1.0, 0.4, alpha)
+            } else {
+                Color::srgba(1.0, 0.3, 0.3, alpha)
+            };
+            gizmos.circle_2d(cursor, radius, color);
+
+            for i in 0..8 {
+                let around = Vec2::from_angle(i as f32 * TAU / 8.0);
+                let (from, to) = if tool.mode == ToolMode::Attract {
+                    (cursor + around * radius, cursor + around * radius * 0.6)
+                } else {
+                    (cursor + around * radius * 0.6, cursor + around * radius)
+                };
+                gizmos.arrow_2d(from, to, color);
+            }
+        }
+        ToolMode::Spawn => {
+            let color = Color::srgba(0.3, 0.6, 1.0, alpha);
+            gizmos.circle_2d(cursor, radius, color);
+            gizmos.line_2d(cursor - Vec2::X * 10.0, cursor + Vec2::X * 10.0, color);
+            gizmos.line_2d(cursor - Vec2::Y * 10.0, cursor + Vec2::Y * 10.0, color);
+        }
+        ToolMode::Erase => {
+            let color = Color::srgba(1.0, 0.6, 0.1, alpha);
+            gizmos.circle_2d(cursor, radius, color);
+            gizmos.line_2d(cursor - Vec2::ONE * 7.0, cursor + Vec2::ONE * 7.0, color);
+            gizmos.line_2d(
+                cursor + Vec2::new(-7.0, 7.0),
+                cursor + Vec2::new(7.0, -7.0),
+                color,
+            );
+        }
+    }
+}
+
+pub fn tool_ui(
+    mut contexts: EguiContexts,
+    mut tool: ResMut<MouseTool>,
+    config: Query<&BoidConfiguration>,
+) {
+    let config = config.single();
+
+    egui::Window::new("tools").show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            for mode in ToolMode::ALL {
+                ui.radio_value(&mut tool.mode, mode, format!("{:?}", mode));
+            }
+        });
+
+        egui::Grid::new("tool_fields").show(ui, |ui| {
+            ui.label("brush_radius");
+            ui.add(egui::Slider::new(&mut tool.brush_radius, 5.0..=500.0f32));
+            ui.end_row();
+
+            ui.label("strength");
+            ui.add(egui::Slider::new(&mut tool.strength, 0.0..=50.0f32));
+            ui.end_row();
+
+            ui.label("species");
+            let selected = config
+                .species
+                .get(tool.species)
+                .map(|species| species.name.clone())
+                .unwrap_or_default();
+            egui::ComboBox::from_id_salt("tool_species")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    for (index, species) in config.species.iter().enumerate() {
+                        ui.selectable_value(&mut tool.species, index, &species.name);
+                    }
+                });
+            ui.end_row();
+        });
+    });
+}
@@ -0,0 +1,498 @@
+use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::boid::RemoveSpecies;
+use crate::config::{
+    BoidConfiguration, BoidGizmoConfig, BoidShape, BoundaryMode, ColorType, Interaction,
+    NeighborMode, SpeciesConfig, TICK_RATES,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::config_file::ConfigFileEditor;
+use crate::export::ExportEditor;
+use crate::flocking::SpatialState;
+use crate::metrics;
+use crate::obstacle::ObstacleEditor;
+use crate::path::PathEditor;
+use crate::perf::{self, IndexComparison};
+use crate::predator::PredatorEditor;
+use crate::preset::PresetEditor;
+use crate::rng::{BoidRng, RestartSimulation};
+use crate::snapshot::SnapshotEditor;
+use crate::time::StepSimulation;
+
+#[allow(clippy::too_many_arguments)]
+pub fn boids_ui(
+    mut config: Query<&mut BoidConfiguration>,
+    mut contexts: EguiContexts,
+    diagnostics: Res<DiagnosticsStore>,
+    spatial_state: Res<State<SpatialState>>,
+    comparison: Res<IndexComparison>,
+    mut next_spatial_state: ResMut<NextState<SpatialState>>,
+    mut restarts: EventWriter<RestartSimulation>,
+    mut removals: EventWriter<RemoveSpecies>,
+    mut rng: ResMut<BoidRng>,
+    mut obstacles: ObstacleEditor,
+    mut predators: PredatorEditor,
+    mut path: PathEditor,
+    mut presets: PresetEditor,
+    #[cfg(not(target_arch = "wasm32"))] mut config_file: ConfigFileEditor,
+    mut snapshots: SnapshotEditor,
+    mut export: ExportEditor,
+) {
+    let mut config = config.single_mut();
+
+    egui::Window::new("boid configuration").show(contexts.ctx_mut(), |ui| {
+        egui::Grid::new("fps").show(ui, |ui| {
+            if let Some(fps) = diagnostics
+                .get(&FrameTimeDiagnosticsPlugin::FPS)
+                .and_then(|fps| fps.smoothed())
+            {
+                ui.label("fps");
+                ui.label(format!("{:.2}", fps));
+                ui.end_row();
+            }
+
+            for (path, label) in metrics::ALL {
+                if let Some(value) = diagnostics.get(&path).and_then(|metric| metric.smoothed()) {
+                    ui.label(label);
+                    ui.label(format!("{:.2}", value));
+                    ui.end_row();
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut config.metrics, "flock metrics");
+            ui.add_enabled(
+                config.metrics,
+                egui::Slider::new(&mut config.metrics_every, 1..=64).text("ticks apart"),
+            );
+        });
+
+        ui.horizontal(|ui| {
+            let mut current = spatial_state.get().clone();
+            ui.radio_value(&mut current, SpatialState::QuadTree, "QuadTree");
+            ui.radio_value(&mut current, SpatialState::SpatialHash, "SpatialHash");
+            ui.radio_value(&mut current, SpatialState::UniformGrid, "UniformGrid");
+
+            if current != *spatial_state.get() {
+                next_spatial_state.set(current);
+            }
+        });
+
+        ui.collapsing("index comparison", |ui| {
+            perf::comparison_ui(ui, &comparison, spatial_state.get());
+        });
+
+        ui.heading("Presets");
+        presets.ui(ui, &mut config);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            ui.heading("Config File");
+            config_file.ui(ui);
+        }
+
+        ui.heading("Snapshots");
+        snapshots.ui(ui, &config);
+
+        ui.heading("Trajectory Export");
+        export.ui(ui);
+
+        ui.heading("Spawning Fields");
+        egui::Grid::new("spawn_fields").show(ui, |ui| {
+            ui.label("boids count");
+            ui.label(config.spawn_count().to_string());
+            ui.end_row();
+
+            ui.label("seed");
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut config.seed));
+                if ui.button("restart").clicked() {
+                    restarts.send(RestartSimulation);
+                }
+                if ui.button("random").clicked() {
+                    config.seed = rand::random();
+                    restarts.send(RestartSimulation);
+                }
+            });
+            ui.end_row();
+        });
+
+        ui.heading("Simulation Fields");
+        egui::Grid::new("simulation_fields").show(ui, |ui| {
+            ui.label("turn_factor");
+            ui.add(bevy_egui::egui::Slider::new(
+                &mut config.turn_factor,
+                0.0..=10.0f32,
+            ));
+            ui.end_row();
+
+            ui.label("boundary_mode");
+            ui.horizontal(|ui| {
+                for mode in BoundaryMode::ALL {
+                    ui.radio_value(&mut config.boundary_mode, mode, format!("{:?}", mode));
+                }
+            });
+            ui.end_row();
+
+            ui.label("view_angle");
+            ui.add(bevy_egui::egui::Slider::new(
+                &mut config.view_angle,
+                0.0..=360.0f32,
+            ));
+            ui.end_row();
+
+            ui.label("distance_falloff");
+            ui.add(bevy_egui::egui::Slider::new(
+                &mut config.distance_falloff,
+                0.0..=1.0f32,
+            ));
+            ui.end_row();
+
+            ui.label("neighbor_mode");
+            ui.horizontal(|ui| {
+                let k = config.neighbor_mode.k().unwrap_or(7);
+                let mode = &mut config.neighbor_mode;
+                if ui.radio(*mode == NeighborMode::Metric, "Metric").clicked() {
+                    *mode = NeighborMode::Metric;
+                }
+                if ui
+                    .radio(
+                        matches!(mode, NeighborMode::Topological { .. }),
+                        "Topological",
+                    )
+                    .clicked()
+                {
+                    *mode = NeighborMode::Topological { k };
+                }
+                if ui
+                    .radio(matches!(mode, NeighborMode::Hybrid { .. }), "Hybrid")
+                    .clicked()
+                {
+                    *mode = NeighborMode::Hybrid { k };
+                }
+                if let NeighborMode::Topological { k } | NeighborMode::Hybrid { k } = mode {
+                    ui.add(bevy_egui::egui::Slider::new(k, 1..=32usize).text("k"));
+                }
+            });
+            ui.end_row();
+        });
+
+        ui.heading("Species");
+        if let Some(index) = species_ui(ui, &mut config) {
+            removals.send(RemoveSpecies(index));
+        }
+        interactions_ui(ui, &mut config);
+
+        egui::Grid::new("gizmos").show(ui, |ui| {
+            ui.heading("Gizmos");
+            ui.end_row();
+
+            boid_ui_for_gizmos(ui, "render_bounds", &mut config.bounds_gizmo);
+            boid_ui_for_gizmos(ui, "render_quadtree", &mut config.quadtree_gizmo);
+            boid_ui_for_gizmos(
+                ui,
+                "render_protected_range",
+                &mut config.protected_range_gizmo,
+            );
+            boid_ui_for_gizmos(ui, "render_visible_range", &mut config.visible_range_gizmo);
+            boid_ui_for_gizmos(ui, "render_obstacles", &mut config.obstacle_gizmo);
+            boid_ui_for_gizmos(ui, "render_path", &mut config.path_gizmo);
+        });
+
+        ui.heading("Obstacles");
+        egui::Grid::new("obstacle_fields").show(ui, |ui| {
+            ui.label("obstacle_lookahead");
+            ui.add(bevy_egui::egui::Slider::new(
+                &mut config.obstacle_lookahead,
+                0.0..=200.0f32,
+            ));
+            ui.end_row();
+
+            ui.label("obstacle_avoid_factor");
+            ui.add(bevy_egui::egui::Slider::new(
+                &mut config.obstacle_avoid_factor,
+                0.0..=10.0f32,
+            ));
+            ui.end_row();
+        });
+        obstacles.ui(ui, &config, &mut rng.editor);
+
+        ui.heading("Predators");
+        egui::Grid::new("predator_fields").show(ui, |ui| {
+            ui.label("fear_range");
+            ui.add(bevy_egui::egui::Slider::new(
+                &mut config.fear_range,
+                0.0..=400.0f32,
+            ));
+            ui.end_row();
+
+            ui.label("flee_factor");
+            ui.add(bevy_egui::egui::Slider::new(
+                &mut config.flee_factor,
+                0.0..=20.0f32,
+            ));
+            ui.end_row();
+
+            ui.label("predator_hunt_range");
+            ui.add(bevy_egui::egui::Slider::new(
+                &mut config.predator_hunt_range,
+                0.0..=1000.0f32,
+            ));
+            ui.end_row();
+
+            ui.label("predator_kill_range");
+            ui.horizontal(|ui| {
+                ui.add(bevy_egui::egui::Slider::new(
+                    &mut config.predator_kill_range,
+                    0.0..=50.0f32,
+                ));
+                ui.checkbox(&mut config.predator_kills, "kills");
+                ui.checkbox(&mut config.predator_respawn_kills, "respawn");
+            });
+            ui.end_row();
+        });
+        predators.ui(ui, &config, &mut rng.editor);
+
+        ui.heading("Path");
+        egui::Grid::new("path_fields").show(ui, |ui| {
+            ui.label("goal_weight");
+            ui.add(bevy_egui::egui::Slider::new(
+                &mut config.goal_weight,
+                0.0..=10.0f32,
+            ));
+            ui.end_row();
+
+            ui.label("waypoint_radius");
+            ui.add(bevy_egui::egui::Slider::new(
+                &mut config.waypoint_radius,
+                0.0..=400.0f32,
+            ));
+            ui.end_row();
+        });
+        path.ui(ui);
+
+        ui.heading("Boid Colors");
+        ui.horizontal(|ui| {
+            ui.label("update_color_sample_rate");
+            ui.add(bevy_egui::egui::Slider::new(
+                &mut config.update_color_sample_rate,
+                0.0..=1.0f32,
+            ));
+        });
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut config.update_color_type, ColorType::Initial, "Initial");
+            ui.radio_value(
+                &mut config.update_color_type,
+                ColorType::Synthwave,
+                "Synthwave",
+            );
+            ui.radio_value(&mut config.update_color_type, ColorType::Pastel, "Pastel");
+            ui.radio_value(
+                &mut config.update_color_type,
+                ColorType::PrimaryRGB,
+                "PrimaryRGB",
+            );
+        });
+    });
+}
+
+/// One collapsing section per species. Returns the species to remove, if any.
+fn species_ui(ui: &mut egui::Ui, config: &mut BoidConfiguration) -> Option<usize> {
+    let mut remove = None;
+    let removable = config.species.len() > 1;
+
+    for (index, species) in config.species.iter_mut().enumerate() {
+        egui::CollapsingHeader::new(species.name.clone())
+            .id_salt(("species", index))
+            .show(ui, |ui| {
+                egui::Grid::new(("species_fields", index)).show(ui, |ui| {
+                    ui.label("name");
+                    ui.text_edit_singleline(&mut species.name);
+                    ui.end_row();
+
+                    ui.label("spawn_count");
+                    ui.add(bevy_egui::egui::Slider::new(
+                        &mut species.spawn_count,
+                        0..=10000u32,
+                    ));
+                    ui.end_row();
+
+                    ui.label("visible_range");
+                    ui.add(bevy_egui::egui::Slider::new(
+                        &mut species.visible_range,
+                        0.0..=100.0f32,
+                    ));
+                    ui.end_row();
+
+                    ui.label("protected_range");
+                    ui.add(bevy_egui::egui::Slider::new(
+                        &mut species.protected_range,
+                        0.0..=100.0f32,
+                    ));
+                    ui.end_row();
+
+                    ui.label("centering_factor");
+                    ui.add(bevy_egui::egui::Slider::new(
+                        &mut species.centering_factor,
+                        0.0..=10.0f32,
+                    ));
+                    ui.end_row();
+
+                    ui.label("avoid_factor");
+                    ui.add(bevy_egui::egui::Slider::new(
+                        &mut species.avoid_factor,
+                        0.0..=10.0f32,
+                    ));
+                    ui.end_row();
+
+                    ui.label("matching_factor");
+                    ui.add(bevy_egui::egui::Slider::new(
+                        &mut species.matching_factor,
+                        0.0..=10.0f32,
+                    ));
+                    ui.end_row();
+
+                    ui.label("max_speed");
+                    let min = species.min_speed;
+                    ui.add(bevy_egui::egui::Slider::new(
+                        &mut species.max_speed,
+                        min..=1000.0f32,
+                    ));
+                    ui.end_row();
+
+                    ui.label("min_speed");
+                    let max = species.max_speed;
+                    ui.add(bevy_egui::egui::Slider::new(
+                        &mut species.min_speed,
+                        0.0..=max,
+                    ));
+                    ui.end_row();
+
+                    ui.label("color");
+                    ui.color_edit_button_rgb(&mut species.color_rgb);
+                    ui.end_row();
+
+                    ui.label("shape");
+                    ui.horizontal(|ui| {
+                        for shape in BoidShape::ALL {
+                            ui.radio_value(&mut species.shape, shape, format!("{:?}", shape));
+                        }
+                    });
+                    ui.end_row();
+                });
+
+                if ui
+                    .add_enabled(removable, egui::Button::new("remove species"))
+                    .clicked()
+                {
+                    remove = Some(index);
+                }
+            });
+    }
+
+    if ui.button("add species").clicked() {
+        let count = config.species.len();
+        config.add_species(SpeciesConfig {
+            name: format!("species {}", count + 1),
+            ..default()
+        });
+    }
+
+    remove
+}
+
+/// The interaction matrix, rows are how that species treats each column.
+fn interactions_ui(ui: &mut egui::Ui, config: &mut BoidConfiguration) {
+    if config.species.len() < 2 {
+        return;
+    }
+
+    let names = config
+        .species
+        .iter()
+        .map(|species| species.name.clone())
+        .collect::<Vec<_>>();
+
+    egui::Grid::new("interactions").show(ui, |ui| {
+        ui.label("");
+        for name in names.iter() {
+            ui.label(name);
+        }
+        ui.end_row();
+
+        for (a, name) in names.iter().enumerate() {
+            ui.label(name);
+            for b in 0..names.len() {
+                let mut interaction = config.interaction(a, b);
+                egui::ComboBox::from_id_salt(("interaction", a, b))
+                    .selected_text(format!("{:?}", interaction))
+                    .show_ui(ui, |ui| {
+                        for option in [Interaction::Align, Interaction::Ignore, Interaction::Avoid]
+                        {
+                            ui.selectable_value(&mut interaction, option, format!("{:?}", option));
+                        }
+                    });
+
+                if interaction != config.interaction(a, b) {
+                    config.interactions[a][b] = interaction;
+                }
+            }
+            ui.end_row();
+        }
+    });
+}
+
+pub fn boid_ui_for_gizmos(ui: &mut bevy_egui::egui::Ui, text: &str, val: &mut BoidGizmoConfig) {
+    ui.checkbox(&mut val.enabled, text);
+    ui.color_edit_button_rgba_unmultiplied(&mut val.color_rgba);
+    ui.end_row();
+}
+
+pub fn time_ui(
+    mut config: Query<&mut BoidConfiguration>,
+    mut contexts: EguiContexts,
+    mut steps: EventWriter<StepSimulation>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+) {
+    let mut config = config.single_mut();
+
+    egui::Window::new("time").show(contexts.ctx_mut(), |ui| {
+        egui::Grid::new("time_fields").show(ui, |ui| {
+            ui.label("tick_rate");
+            ui.add(bevy_egui::egui::Slider::new(
+                &mut config.tick_rate,
+                TICK_RATES,
+            ));
+            ui.end_row();
+
+            ui.label("time_scale");
+            let mut time_scale = virtual_time.relative_speed();
+            if ui
+                .add(bevy_egui::egui::Slider::new(&mut time_scale, 0.1..=4.0f32))
+                .changed()
+            {
+                virtual_time.set_relative_speed(time_scale);
+            }
+            ui.end_row();
+
+            ui.label("paused");
+            ui.horizontal(|ui| {
+                let mut paused = virtual_time.is_paused();
+                if ui.checkbox(&mut paused, "").changed() {
+                    if paused {
+                        virtual_time.pause();
+                    } else {
+                        virtual_time.unpause();
+                    }
+                }
+                if ui.add_enabled(paused, egui::Button::new("step")).clicked() {
+                    steps.send(StepSimulation);
+                }
+            });
+            ui.end_row();
+        });
+    });
+}
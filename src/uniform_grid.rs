@@ -0,0 +1,232 @@
+use bevy::math::{Rect, UVec2, Vec2};
+use bevy::prelude::Resource;
+
+use crate::spatial::SpatialIndex;
+
+/// Most cells a grid will lay out. Past this `resize` makes the cells bigger instead, so a tiny
+/// cell size over large bounds can't allocate without limit.
+pub const MAX_CELLS: u32 = 1 << 22;
+
+/// Dense grid of square cells over `bounds`, rebuilt each frame with a counting sort.
+///
+/// Points are stored sorted by cell in one flat array, with `cell_start[i]..cell_start[i + 1]`
+/// holding cell `i`. Points outside `bounds` are clamped into the edge cells so none are dropped.
+/// The buffers are reused between builds, so after the first frame neither building nor querying
+/// allocates.
+#[derive(Resource, Debug)]
+pub struct UniformGrid<T> {
+    bounds: Rect,
+    cell_size: f32,
+    cells: UVec2,
+    cell_start: Vec<u32>,
+    cell_of: Vec<u32>,
+    order: Vec<u32>,
+    unsorted: Vec<(Vec2, T)>,
+    entries: Vec<(Vec2, T)>,
+}
+
+impl<T> UniformGrid<T> {
+    pub fn new(bounds: Rect, cell_size: f32) -> Self {
+        let mut grid = UniformGrid {
+            bounds,
+            cell_size,
+            cells: UVec2::ONE,
+            cell_start: vec![],
+            cell_of: vec![],
+            order: vec![],
+            unsorted: vec![],
+            entries: vec![],
+        };
+        grid.resize(bounds, cell_size);
+        grid
+    }
+
+    /// Changes the grid layout. Takes effect on the next `build`.
+    pub fn resize(&mut self, bounds: Rect, cell_size: f32) {
+        self.bounds = bounds;
+        self.cell_size = cell_size.max(f32::EPSILON);
+
+        let cells = (bounds.size() / self.cell_size).max(Vec2::ONE);
+        let count = cells.x as f64 * cells.y as f64;
+        if count > MAX_CELLS as f64 {
+            self.cell_size *= (count / MAX_CELLS as f64).sqrt() as f32;
+        }
+
+        self.cells = (bounds.size() / self.cell_size)
+            .ceil()
+            .max(Vec2::ONE)
+            .as_uvec2();
+        // rounding up can still tip over by a row or column
+        while self.cells.x * self.cells.y > MAX_CELLS {
+            self.cell_size *= 1.01;
+            self.cells = (bounds.size() / self.cell_size)
+                .ceil()
+                .max(Vec2::ONE)
+                .as_uvec2();
+        }
+    }
+
+    pub fn bounds(&self) -> Rect {
+        self.bounds
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    fn cell(&self, point: Vec2) -> UVec2 {
+        ((point - self.bounds.min) / self.cell_size)
+            .floor()
+            .clamp(Vec2::ZERO, (self.cells - 1).as_vec2())
+            .as_uvec2()
+    }
+
+    fn index(&self, cell: UVec2) -> usize {
+        (cell.y * self.cells.x + cell.x) as usize
+    }
+}
+
+impl<T: Clone> SpatialIndex<T> for UniformGrid<T> {
+    fn build(&mut self, points: impl IntoIterator<Item = (Vec2, T)>) {
+        self.unsorted.clear();
+        self.unsorted.extend(points);
+
+        let num_cells = (self.cells.x * self.cells.y) as usize;
+        self.cell_start.clear();
+        self.cell_start.resize(num_cells + 1, 0);
+
+        // count the points in each cell
+        self.cell_of.clear();
+        for i in 0..self.unsorted.len() {
+            let cell = self.index(self.cell(self.unsorted[i].0));
+            self.cell_of.push(cell as u32);
+            self.cell_start[cell + 1] += 1;
+        }
+
+        // prefix sum turns the counts into the start offset of each cell
+        for i in 0..num_cells {
+            self.cell_start[i + 1] += self.cell_start[i];
+        }
+
+        // scatter, using the start offsets as write cursors
+        self.order.clear();
+        self.order.resize(self.unsorted.len(), 0);
+        for (i, cell) in self.cell_of.iter().enumerate() {
+            let cursor = &mut self.cell_start[*cell as usize];
+            self.order[*cursor as usize] = i as u32;
+            *cursor += 1;
+        }
+
+        // the cursors now sit at the end of each cell, shift them back to the starts
+        for i in (1..=num_cells).rev() {
+            self.cell_start[i] = self.cell_start[i - 1];
+        }
+        self.cell_start[0] = 0;
+
+        self.entries.clear();
+        self.entries.extend(
+            self.order
+                .iter()
+                .map(|i| self.unsorted[*i as usize].clone()),
+        );
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn query_radius(&self, center: Vec2, radius: f32, mut visit: impl FnMut(Vec2, &T)) -> usize {
+        if self.entries.is_empty() {
+            return 0;
+        }
+
+        let min = self.cell(center - radius);
+        let max = self.cell(center + radius);
+        let radius_squared = radius * radius;
+        let mut examined = 0;
+
+        for y in min.y..=max.y {
+            let row = self.index(UVec2::new(0, y));
+            let start = self.cell_start[row + min.x as usize] as usize;
+            let end = self.cell_start[row + max.x as usize + 1] as usize;
+
+            // cells in a row are contiguous, so the whole span is one slice
+            examined += end - start;
+            for (point, value) in &self.entries[start..end] {
+                if point.distance_squared(center) <= radius_squared {
+                    visit(*point, value);
+                }
+            }
+        }
+        examined
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::math::{Rect, Vec2};
+
+    use crate::spatial::SpatialIndex;
+    use crate::uniform_grid::{UniformGrid, MAX_CELLS};
+
+    #[test]
+    fn query_radius_matches_brute_force() {
+        let points: Vec<(Vec2, usize)> = (0..500)
+            .map(|i| {
+                let angle = i as f32 * 0.7;
+                let distance = (i % 37) as f32 * 4.0;
+                (Vec2::from_angle(angle) * distance, i)
+            })
+            .collect();
+
+        let mut grid = UniformGrid::new(Rect::new(-100.0, -100.0, 100.0, 100.0), 15.0);
+        grid.build(points.iter().cloned());
+
+        for center in [Vec2::ZERO, Vec2::new(90.0, -90.0), Vec2::new(300.0, 0.0)] {
+            let mut found = vec![];
+            grid.query_radius(center, 40.0, |_, value| found.push(*value));
+            found.sort();
+
+            let expected: Vec<usize> = points
+                .iter()
+                .filter(|(point, _)| point.distance_squared(center) <= 40.0 * 40.0)
+                .map(|(_, value)| *value)
+                .collect();
+
+            assert_eq!(found, expected, "center {:?}", center);
+        }
+    }
+
+    #[test]
+    fn caps_the_cell_count() {
+        let bounds = Rect::new(-7680.0, -4320.0, 7680.0, 4320.0);
+        for cell_size in [1.0, 0.0] {
+            let mut grid = UniformGrid::new(bounds, cell_size);
+            let cells = grid.cells.x as u64 * grid.cells.y as u64;
+            assert!(
+                cells <= MAX_CELLS as u64,
+                "{} cells at {}",
+                cells,
+                cell_size
+            );
+
+            grid.build([(Vec2::new(1.0, 1.0), 1)]);
+            let mut found = vec![];
+            grid.query_radius(Vec2::ZERO, 5.0, |_, value| found.push(*value));
+            assert_eq!(found, vec![1]);
+        }
+    }
+
+    #[test]
+    fn keeps_points_outside_bounds() {
+        let mut grid = UniformGrid::new(Rect::new(-10.0, -10.0, 10.0, 10.0), 5.0);
+        grid.build([(Vec2::new(50.0, 50.0), 1), (Vec2::new(51.0, 50.0), 2)]);
+
+        let mut found = vec![];
+        grid.query_radius(Vec2::new(50.0, 50.0), 2.0, |_, value| found.push(*value));
+        found.sort();
+
+        assert_eq!(grid.len(), 2);
+        assert_eq!(found, vec![1, 2]);
+    }
+}
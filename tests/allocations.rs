@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use rs_boids::{BoidsPlugin, CountingAllocator, IndexComparison, IndexProfile, SpatialState};
+
+// a test binary of its own, so the lib tests keep the system allocator
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[test]
+fn counts_index_allocations() {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+            16,
+        )))
+        .add_plugins(BoidsPlugin {
+            spawn_count: 200,
+            seed: Some(5),
+            ..BoidsPlugin::headless()
+        });
+
+    let states = [
+        SpatialState::QuadTree,
+        SpatialState::SpatialHash,
+        SpatialState::UniformGrid,
+    ];
+    for state in states.iter() {
+        app.world_mut()
+            .resource_mut::<NextState<SpatialState>>()
+            .set(state.clone());
+        for _ in 0..10 {
+            app.update();
+        }
+    }
+
+    let comparison = &app.world().resource::<IndexComparison>().0;
+    for state in states.iter() {
+        let stats = comparison.get(state).expect("every index ran");
+        assert!(stats.allocations.is_some(), "{:?}", state);
+    }
+
+    // the grid is last, so it has warmed up: its buffers are sized and only reused from here on
+    for _ in 0..20 {
+        app.update();
+        let allocations = app.world().resource::<IndexProfile>().allocations;
+        assert_eq!(allocations, 0, "the uniform grid allocated during a tick");
+    }
+}